@@ -0,0 +1,81 @@
+import { createPublicClient, createWalletClient, http, parseAbi } from "viem";
+import { privateKeyToAccount } from "viem/accounts";
+
+interface ScriptParams {
+  parentChainRpc: string;
+  ownerPrivateKey: `0x${string}`;
+  upgradeExecutor: `0x${string}`;
+  sequencerInbox: `0x${string}`;
+  setValidKeyset: `0x${string}`;
+  invalidateKeysetHash?: `0x${string}`;
+}
+
+const upgradeExecutorAbi = parseAbi([
+  "function executeCall(address target, bytes targetCallData) payable",
+]);
+
+function validateParams(params: unknown): asserts params is ScriptParams {
+  if (!params || typeof params !== "object") {
+    throw new Error("Invalid params: must be an object");
+  }
+
+  const requiredFields = [
+    "parentChainRpc",
+    "ownerPrivateKey",
+    "upgradeExecutor",
+    "sequencerInbox",
+    "setValidKeyset",
+  ];
+
+  for (const field of requiredFields) {
+    if (!(field in params)) {
+      throw new Error(`Missing required field: ${field}`);
+    }
+  }
+}
+
+async function main() {
+  const params: ScriptParams = JSON.parse(process.argv[2]);
+  validateParams(params);
+
+  const parentChainClient = createPublicClient({
+    transport: http(params.parentChainRpc),
+  });
+
+  const account = privateKeyToAccount(params.ownerPrivateKey);
+  const walletClient = createWalletClient({
+    account,
+    transport: http(params.parentChainRpc),
+  });
+
+  // The SequencerInbox only accepts keyset changes from its owner, the UpgradeExecutor
+  const calls = [params.setValidKeyset];
+  if (params.invalidateKeysetHash) {
+    calls.push(params.invalidateKeysetHash);
+  }
+
+  try {
+    const transactionHashes: string[] = [];
+    for (const targetCallData of calls) {
+      const hash = await walletClient.writeContract({
+        address: params.upgradeExecutor,
+        abi: upgradeExecutorAbi,
+        functionName: "executeCall",
+        args: [params.sequencerInbox, targetCallData],
+        chain: null,
+      });
+      await parentChainClient.waitForTransactionReceipt({ hash });
+      transactionHashes.push(hash);
+    }
+
+    console.log(JSON.stringify({ transactionHashes }));
+  } catch (error) {
+    console.error(error);
+    process.exit(1);
+  }
+}
+
+main().catch((error) => {
+  console.error(error);
+  process.exit(1);
+});
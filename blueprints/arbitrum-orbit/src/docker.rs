@@ -0,0 +1,294 @@
+use std::path::PathBuf;
+
+use alloy_primitives::TxHash;
+use alloy_provider::Provider;
+use alloy_sol_types::{sol, SolEvent};
+use color_eyre::eyre::{eyre, Result};
+use gadget_sdk::docker::connect_to_docker;
+use gadget_sdk::utils::evm::get_provider_http;
+use orbit_sequencer_deployer::node_config::{
+    parent_chain_is_l1, ChainConfig, ChainInfo, CoreContracts, StakerStrategy,
+};
+use orbit_sequencer_deployer::{
+    DasCommitteeConfig, DasStorage, DockerTags, Endpoint, EnvFiles, ExplorerSettings,
+    NodeEndpoints, NodeRole, OrbitStack, OrbitStackConfig, ProxyConfig, StackEvent, StackProfile,
+    TlsConfig,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::jobs::{register_keyset, ServiceContext};
+use crate::{OrbitDeploymentResult, OrbitRollupConfig};
+
+/// Default root under which each chain gets its own data directory.
+const DEFAULT_DATA_ROOT: &str = "./data";
+
+sol!(
+    #[allow(missing_docs)]
+    event RollupCreated(
+        address indexed rollupAddress,
+        address indexed nativeToken,
+        address inboxAddress,
+        address outbox,
+        address rollupEventInbox,
+        address challengeManager,
+        address adminProxy,
+        address sequencerInbox,
+        address bridge,
+        address upgradeExecutor,
+        address validatorUtils,
+        address validatorWalletCreator
+    );
+);
+
+/// Reads the core contracts of a deployment from the `RollupCreated` event in its receipt.
+pub async fn fetch_core_contracts(
+    deployment: &OrbitDeploymentResult,
+    parent_chain_rpc: &str,
+) -> Result<CoreContracts> {
+    let provider = get_provider_http(parent_chain_rpc);
+    let tx_hash: TxHash = deployment.transaction_hash.parse()?;
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await?
+        .ok_or_else(|| eyre!("No receipt found for deployment {}", tx_hash))?;
+    let deployed_at = receipt
+        .block_number
+        .ok_or_else(|| eyre!("Deployment {} is still pending", tx_hash))?;
+
+    let event = receipt
+        .inner
+        .logs()
+        .iter()
+        .find_map(|log| RollupCreated::decode_log(&log.inner, true).ok())
+        .ok_or_else(|| eyre!("Deployment {} emitted no RollupCreated event", tx_hash))?
+        .data;
+
+    Ok(CoreContracts {
+        rollup: event.rollupAddress,
+        native_token: event.nativeToken,
+        inbox: event.inboxAddress,
+        outbox: event.outbox,
+        rollup_event_inbox: event.rollupEventInbox,
+        challenge_manager: event.challengeManager,
+        admin_proxy: event.adminProxy,
+        sequencer_inbox: event.sequencerInbox,
+        bridge: event.bridge,
+        upgrade_executor: event.upgradeExecutor,
+        validator_utils: event.validatorUtils,
+        validator_wallet_creator: event.validatorWalletCreator,
+        deployed_at,
+    })
+}
+
+/// Derives the chain info of a freshly deployed rollup from its core contracts.
+fn chain_info(
+    config: &OrbitRollupConfig,
+    chain_name: &str,
+    core_contracts: &CoreContracts,
+) -> ChainInfo {
+    let chain_config = ChainConfig::new(
+        config.chain_id,
+        config.owner,
+        config.data_availability_committee,
+    );
+
+    ChainInfo::new(
+        chain_name.to_string(),
+        chain_config,
+        core_contracts,
+        config.parent_chain_id,
+    )
+}
+
+/// Builds the stack configuration for the sequencer of a deployed rollup, with the operator
+/// also posting batches and validating.
+fn stack_config(
+    config: &OrbitRollupConfig,
+    chain_name: String,
+    chain_info_json: String,
+    data_dir: PathBuf,
+) -> Result<OrbitStackConfig> {
+    // Blobs are only available on an L1, and AnyTrust chains keep batch data with the committee
+    let post_4844_blobs =
+        parent_chain_is_l1(config.parent_chain_id) && !config.data_availability_committee;
+
+    Ok(OrbitStackConfig {
+        parent_chain_id: config.parent_chain_id,
+        parent_chain_rpc: std::env::var("PARENT_CHAIN_RPC")?,
+        parent_chain_beacon_rpc: std::env::var("PARENT_CHAIN_BEACON_RPC").ok(),
+        chain_id: config.chain_id,
+        chain_name,
+        chain_info_json,
+        roles: vec![
+            NodeRole::Sequencer,
+            NodeRole::BatchPoster {
+                private_key: std::env::var("BATCH_POSTER_PRIVATE_KEY")?,
+                post_4844_blobs,
+            },
+            NodeRole::Validator {
+                private_key: std::env::var("VALIDATOR_PRIVATE_KEY")?,
+                strategy: StakerStrategy::MakeNodes,
+            },
+        ],
+        // WebSocket RPC and the feed are reached through the proxy
+        endpoints: NodeEndpoints {
+            ws: Endpoint {
+                enable: true,
+                publish: None,
+            },
+            ..Default::default()
+        },
+        enable_das: config.data_availability_committee,
+        profile: match std::env::var("ORBIT_STACK_PROFILE") {
+            Ok(profile) => profile.parse()?,
+            Err(_) => StackProfile::Full,
+        },
+        explorer_services: None,
+        docker_tags: DockerTags::default(),
+        // Env files are optional overrides of the generated explorer env
+        env_files: EnvFiles::existing_in(&data_dir.join("env")),
+        explorer: ExplorerSettings {
+            public_host: std::env::var("EXPLORER_PUBLIC_HOST")
+                .unwrap_or_else(|_| "localhost".to_string()),
+            ..Default::default()
+        },
+        proxy: ProxyConfig {
+            rpc_domain: std::env::var("RPC_PUBLIC_HOST").ok(),
+            tls: match (
+                std::env::var("TLS_CERTIFICATE"),
+                std::env::var("TLS_PRIVATE_KEY"),
+            ) {
+                (Ok(certificate), Ok(private_key)) => Some(TlsConfig {
+                    certificate: certificate.into(),
+                    private_key: private_key.into(),
+                }),
+                _ => None,
+            },
+            ..Default::default()
+        },
+        resources: Default::default(),
+        platforms: Default::default(),
+        supervisor: Default::default(),
+        log_shipping: Default::default(),
+        data_dir,
+        sequencer_endpoint: None,
+        das_endpoints: None,
+        das_online_url_list: None,
+        // The operator runs a single-member committee for AnyTrust chains
+        das_committee: config
+            .data_availability_committee
+            .then(|| DasCommitteeConfig {
+                members: 1,
+                external_members: Vec::new(),
+                assumed_honest: 1,
+                storage: DasStorage::LocalFile,
+                host_port_base: DasCommitteeConfig::DEFAULT_HOST_PORT_BASE,
+            }),
+        das_mirror: None,
+        extra_node_args: Vec::new(),
+    })
+}
+
+/// Brings up the node stack for a deployed rollup and records its RPC endpoint in the
+/// service context.
+pub async fn start_node_stack(
+    deployment: &OrbitDeploymentResult,
+    config: &OrbitRollupConfig,
+    context: &ServiceContext,
+) -> Result<String> {
+    let chain_name = format!("orbit-{}", config.chain_id);
+    let data_dir = PathBuf::from(
+        std::env::var("ORBIT_DATA_DIR").unwrap_or_else(|_| DEFAULT_DATA_ROOT.to_string()),
+    )
+    .join(&chain_name);
+    std::fs::create_dir_all(&data_dir)?;
+    // Docker takes a relative bind source for a volume name, so the data is mounted by its
+    // absolute path
+    let data_dir = std::fs::canonicalize(data_dir)?;
+
+    let core_contracts =
+        fetch_core_contracts(deployment, &std::env::var("PARENT_CHAIN_RPC")?).await?;
+    let chain_info = chain_info(config, &chain_name, &core_contracts);
+    *context.core_contracts.lock().await = Some(core_contracts.clone());
+    let chain_info_json = chain_info.to_info_json()?;
+    let stack_config = stack_config(config, chain_name, chain_info_json, data_dir)?;
+
+    let docker = connect_to_docker(None).await?;
+    let mut stack = OrbitStack::new(stack_config, docker);
+
+    // Report the progress of the stack and the crashes of its services
+    let mut events = stack.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(StackEvent::Stopped { service, exit_code }) => {
+                    gadget_sdk::info!("Service {} stopped with exit code {:?}", service, exit_code)
+                }
+                Ok(event) => gadget_sdk::info!("Node stack: {:?}", event),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+
+    stack.start_with_dependencies().await?;
+
+    // A batch poster whose committee keyset is not registered cannot post
+    if let Err(e) = register_das_keyset(&stack, &core_contracts).await {
+        stack.cleanup().await?;
+        return Err(e);
+    }
+
+    let rpc_endpoint = stack.rpc_endpoint();
+    *context.rpc_endpoint.lock().await = Some(rpc_endpoint.clone());
+    *context.stack.lock().await = Some(stack);
+
+    // Keep the stack alive for as long as it is set in the context
+    let supervisor = OrbitStack::spawn_supervisor(context.stack.clone());
+    context
+        .metrics
+        .count_supervisor_restarts(supervisor.subscribe());
+
+    Ok(rpc_endpoint)
+}
+
+/// Registers the keyset of the committee run by the stack on the SequencerInbox, unless it
+/// already is, retiring the one registered before.
+async fn register_das_keyset(stack: &OrbitStack, core_contracts: &CoreContracts) -> Result<()> {
+    let Some(keyset) = stack.das_keyset() else {
+        return Ok(());
+    };
+    let registered = stack.registered_das_keyset_hash()?;
+    if registered == Some(keyset.keyset_hash) {
+        return Ok(());
+    }
+
+    gadget_sdk::info!("Registering DAS keyset {}", keyset.keyset_hash);
+    register_keyset(core_contracts, keyset, registered)
+        .await
+        .map_err(|e| {
+            eyre!(
+                "Failed to register DAS keyset {}: {}",
+                keyset.keyset_hash,
+                e
+            )
+        })?;
+    stack.record_registered_das_keyset(keyset.keyset_hash)?;
+    Ok(())
+}
+
+/// Tears the node stack down once the service ends. Its data is deleted as well when
+/// `ORBIT_PURGE_ON_EXIT` names the chain, such as `orbit-412346`, confirming the purge.
+pub async fn stop_node_stack(context: &ServiceContext) -> Result<()> {
+    // Taking the stack out of the context also ends its supervision
+    let Some(mut stack) = context.stack.lock().await.take() else {
+        return Ok(());
+    };
+    *context.rpc_endpoint.lock().await = None;
+
+    match std::env::var("ORBIT_PURGE_ON_EXIT") {
+        Ok(chain_name) => stack.purge(&chain_name).await?,
+        Err(_) => stack.cleanup().await?,
+    }
+    Ok(())
+}
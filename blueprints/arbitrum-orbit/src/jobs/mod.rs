@@ -1,15 +1,32 @@
-use alloy_primitives::Address;
+use alloy_primitives::{Address, Bytes, B256};
 use api::services::events::JobCalled;
 use gadget_sdk as sdk;
+use orbit_sequencer_deployer::{
+    invalidate_keyset_calldata,
+    node_config::{CoreContracts, DasBackend},
+    DasKeyset, OrbitStack,
+};
 use sdk::event_listener::tangle::{jobs::services_pre_processor, TangleEventListener};
 use sdk::tangle_subxt::tangle_testnet_runtime::api;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::process::Command;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::metrics::Metrics;
 
 #[derive(Clone)]
 pub struct ServiceContext {
     pub config: sdk::config::StdGadgetConfiguration,
+    pub stack: Arc<Mutex<Option<OrbitStack>>>,
+    pub rpc_endpoint: Arc<Mutex<Option<String>>>,
+    /// Core contracts of the rollup, once it has been deployed.
+    pub core_contracts: Arc<Mutex<Option<CoreContracts>>>,
+    /// Held for the whole of a keyset rotation, as rotations share the keys generated for the
+    /// next keyset.
+    pub das_rotation: Arc<Mutex<()>>,
+    pub metrics: Arc<Metrics>,
 }
 
 // Parameters for validator management
@@ -51,6 +68,28 @@ pub struct FeeRecipientParams {
     pub weights: Vec<u64>,
 }
 
+// Parameters for AnyTrust committee keyset rotation. The members run by the node stack are
+// always part of the committee and get fresh keys; `members` lists those run elsewhere.
+#[derive(Serialize, Deserialize)]
+pub struct DasKeysetParams {
+    pub members: Vec<DasBackend>,
+    pub assumed_honest: u64,
+    pub das_endpoints: Option<Vec<String>>,
+    pub das_online_url_list: Option<String>,
+}
+
+// Parameters for the keyset rotation script
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeysetUpdateParams {
+    pub parent_chain_rpc: String,
+    pub owner_private_key: String,
+    pub upgrade_executor: Address,
+    pub sequencer_inbox: Address,
+    pub set_valid_keyset: Bytes,
+    pub invalidate_keyset_hash: Option<Bytes>,
+}
+
 macro_rules! create_job {
     ($id:expr, $name:ident, $params_type:ty) => {
         #[sdk::job(
@@ -62,6 +101,7 @@ macro_rules! create_job {
                             ),
                         )]
         pub fn $name(params_bytes: Vec<u8>, context: ServiceContext) -> Result<String, Infallible> {
+            let timer = context.metrics.start_job($id);
             let params: $params_type = serde_json::from_slice(&params_bytes).expect(&format!(
                 "Failed to deserialize {} params",
                 stringify!($name)
@@ -76,6 +116,11 @@ macro_rules! create_job {
                 .output()
                 .expect("Failed to execute script");
 
+            timer.finish(if output.status.success() {
+                "success"
+            } else {
+                "failure"
+            });
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         }
     };
@@ -92,3 +137,98 @@ create_job!(3, configure_fast_withdrawals, FastWithdrawalParams);
 
 // Job to configure fee recipients
 create_job!(4, configure_fee_recipients, FeeRecipientParams);
+
+// Job to rotate the AnyTrust committee keyset
+#[sdk::job(
+    id = 5,
+    params(params_bytes),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = services_pre_processor,
+    ),
+)]
+pub async fn rotate_das_keyset(
+    params_bytes: Vec<u8>,
+    context: ServiceContext,
+) -> Result<String, std::io::Error> {
+    let timer = context.metrics.start_job(5);
+    let result = rotate_keyset(&params_bytes, &context).await;
+    timer.finish(if result.is_ok() { "success" } else { "failure" });
+    result
+}
+
+fn job_error(msg: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, msg.to_string())
+}
+
+async fn rotate_keyset(params_bytes: &[u8], context: &ServiceContext) -> std::io::Result<String> {
+    let params: DasKeysetParams = serde_json::from_slice(params_bytes)?;
+    let _rotation = context.das_rotation.lock().await;
+    let core_contracts = context
+        .core_contracts
+        .lock()
+        .await
+        .clone()
+        .ok_or_else(|| job_error("Rollup is not deployed"))?;
+
+    // Only the encoding needs the stack; it stays free while the keyset is registered
+    let (keyset, previous) = {
+        let stack = context.stack.lock().await;
+        let stack = stack
+            .as_ref()
+            .ok_or_else(|| job_error("Node stack is not running"))?;
+        let keyset = stack
+            .encode_das_keyset(params.assumed_honest, params.members)
+            .await
+            .map_err(job_error)?;
+        let previous = stack.registered_das_keyset_hash().map_err(job_error)?;
+        (keyset, previous)
+    };
+
+    // Keep the node on the old committee unless the new keyset is registered
+    let output = register_keyset(&core_contracts, &keyset, previous).await?;
+
+    context
+        .stack
+        .lock()
+        .await
+        .as_mut()
+        .ok_or_else(|| job_error("Node stack is not running"))?
+        .update_das_committee(keyset, params.das_endpoints, params.das_online_url_list)
+        .await
+        .map_err(job_error)?;
+
+    Ok(output)
+}
+
+/// Registers a committee keyset on the SequencerInbox through the UpgradeExecutor, retiring
+/// the `previous` keyset if it is another one, and returns the script's output.
+pub(crate) async fn register_keyset(
+    core_contracts: &CoreContracts,
+    keyset: &DasKeyset,
+    previous: Option<B256>,
+) -> std::io::Result<String> {
+    let script_params = KeysetUpdateParams {
+        parent_chain_rpc: std::env::var("PARENT_CHAIN_RPC").map_err(job_error)?,
+        owner_private_key: std::env::var("OWNER_PRIVATE_KEY").map_err(job_error)?,
+        upgrade_executor: core_contracts.upgrade_executor,
+        sequencer_inbox: core_contracts.sequencer_inbox,
+        set_valid_keyset: keyset.set_valid_keyset_calldata(),
+        invalidate_keyset_hash: previous
+            .filter(|previous| *previous != keyset.keyset_hash)
+            .map(invalidate_keyset_calldata),
+    };
+    let output = tokio::process::Command::new("node")
+        .arg("scripts/rotate-das-keyset.ts")
+        .arg(serde_json::to_string(&script_params)?)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(job_error(format!(
+            "Keyset registration failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -8,7 +8,9 @@ use jobs::{set_validators, ServiceContext, TokenBridgeParams, ValidatorParams};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+pub mod docker;
 pub mod jobs;
+pub mod metrics;
 
 sol!(
     #[allow(missing_docs)]
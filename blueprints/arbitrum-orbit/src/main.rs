@@ -1,22 +1,34 @@
+use std::sync::Arc;
+
 use alloy_primitives::Address;
 use arbitrum_orbit_blueprint::{
     deploy_rollup,
+    docker::{start_node_stack, stop_node_stack},
     jobs::{
         AddExecutorsEventHandler, ConfigureFastWithdrawalsEventHandler,
-        ConfigureFeeRecipientsEventHandler, ServiceContext, SetValidatorsEventHandler,
+        ConfigureFeeRecipientsEventHandler, RotateDasKeysetEventHandler, ServiceContext,
+        SetValidatorsEventHandler,
     },
+    metrics::{self, Metrics},
     setup_initial_configuration, OrbitRaaSBlueprint, OrbitRollupConfig,
 };
 use color_eyre::Result;
 use gadget_sdk::{self as sdk, utils::evm::get_provider_http};
 use sdk::runners::tangle::TangleConfig;
 use sdk::runners::BlueprintRunner;
+use tokio::sync::Mutex;
 
 #[sdk::main(env)]
 async fn main() -> Result<()> {
     let context = ServiceContext {
         config: env.clone(),
+        stack: Arc::new(Mutex::new(None)),
+        rpc_endpoint: Arc::new(Mutex::new(None)),
+        core_contracts: Arc::new(Mutex::new(None)),
+        das_rotation: Arc::new(Mutex::new(())),
+        metrics: Arc::new(Metrics::default()),
     };
+    metrics::serve(context.clone()).await?;
 
     let service_id = env.service_id().unwrap();
     let provider = get_provider_http(&env.http_rpc_endpoint);
@@ -45,6 +57,13 @@ async fn main() -> Result<()> {
         setup_token_bridge: rollup_config_return.setupTokenBridge,
         native_token_is_erc20: rollup_config_return.nativeTokenIsERC20,
     };
+    context.metrics.watch_operator("owner", rollup_config.owner);
+    context
+        .metrics
+        .watch_operator_key("batch_poster", &std::env::var("BATCH_POSTER_PRIVATE_KEY")?)?;
+    context
+        .metrics
+        .watch_operator_key("validator", &std::env::var("VALIDATOR_PRIVATE_KEY")?)?;
 
     gadget_sdk::info!("Starting rollup deployment...");
 
@@ -55,23 +74,32 @@ async fn main() -> Result<()> {
     setup_initial_configuration(&deployment_result, &rollup_config, &context).await?;
     gadget_sdk::info!("Initial configuration completed");
 
+    // Bring up the sequencer and its supporting services
+    let rpc_endpoint = start_node_stack(&deployment_result, &rollup_config, &context).await?;
+    gadget_sdk::info!("Node stack started, RPC available at {}", rpc_endpoint);
+
     // Initialize all jobs
     let set_validators = SetValidatorsEventHandler::new(&env, context.clone()).await?;
     let add_executors = AddExecutorsEventHandler::new(&env, context.clone()).await?;
     let configure_fast_withdrawals =
         ConfigureFastWithdrawalsEventHandler::new(&env, context.clone()).await?;
-    let configure_fee_recipients = ConfigureFeeRecipientsEventHandler::new(&env, context).await?;
+    let configure_fee_recipients =
+        ConfigureFeeRecipientsEventHandler::new(&env, context.clone()).await?;
+    let rotate_das_keyset = RotateDasKeysetEventHandler::new(&env, context.clone()).await?;
 
     // Start the event watcher
     let tangle_config = TangleConfig::default();
-    BlueprintRunner::new(tangle_config, env)
+    let result = BlueprintRunner::new(tangle_config, env)
         .job(set_validators)
         .job(add_executors)
         .job(configure_fast_withdrawals)
         .job(configure_fee_recipients)
+        .job(rotate_das_keyset)
         .run()
-        .await?;
+        .await;
 
     gadget_sdk::info!("Exiting...");
+    stop_node_stack(&context).await?;
+    result?;
     Ok(())
 }
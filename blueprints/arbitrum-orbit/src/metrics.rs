@@ -0,0 +1,493 @@
+//! Prometheus metrics of the blueprint: job runs, the services of the node stack and the
+//! balances of the operator accounts on the parent chain, served in the text exposition format.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use alloy_primitives::Address;
+use alloy_provider::Provider;
+use alloy_signer_local::PrivateKeySigner;
+use color_eyre::eyre::Result;
+use gadget_sdk::utils::evm::get_provider_http;
+use orbit_sequencer_deployer::{Health, SupervisorEvent};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+
+use crate::jobs::ServiceContext;
+
+/// Address the metrics are served on unless `METRICS_ADDR` is set. Only reachable locally.
+pub const DEFAULT_METRICS_ADDR: &str = "127.0.0.1:9400";
+
+/// Upper bounds of the job duration buckets, in seconds.
+const JOB_BUCKETS: &[f64] = &[0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0];
+
+#[derive(Default)]
+struct JobStats {
+    count: u64,
+    sum: f64,
+    /// Observations per bucket of `JOB_BUCKETS`, not cumulated.
+    buckets: [u64; JOB_BUCKETS.len()],
+}
+
+/// Balance of an operator account, with how much it has decreased since the blueprint started.
+#[derive(Default)]
+struct OperatorBalance {
+    balance: Option<f64>,
+    decrease: f64,
+}
+
+/// Metrics recorded by the blueprint between scrapes.
+#[derive(Default)]
+pub struct Metrics {
+    jobs: Mutex<BTreeMap<(u64, &'static str), JobStats>>,
+    supervisor_restarts: Mutex<BTreeMap<String, u64>>,
+    operators: Mutex<BTreeMap<(String, Address), OperatorBalance>>,
+}
+
+/// Times a job run and records it on finish, or as `panicked` if the job unwinds first.
+pub struct JobTimer<'a> {
+    metrics: &'a Metrics,
+    job_id: u64,
+    start: Instant,
+    finished: bool,
+}
+
+impl JobTimer<'_> {
+    pub fn finish(mut self, outcome: &'static str) {
+        self.finished = true;
+        self.metrics
+            .record_job(self.job_id, outcome, self.start.elapsed());
+    }
+}
+
+impl Drop for JobTimer<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.metrics
+                .record_job(self.job_id, "panicked", self.start.elapsed());
+        }
+    }
+}
+
+impl Metrics {
+    pub fn start_job(&self, job_id: u64) -> JobTimer<'_> {
+        JobTimer {
+            metrics: self,
+            job_id,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    pub fn record_job(&self, job_id: u64, outcome: &'static str, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = jobs.entry((job_id, outcome)).or_default();
+        stats.count += 1;
+        stats.sum += secs;
+        if let Some(bucket) = JOB_BUCKETS.iter().position(|bound| secs <= *bound) {
+            stats.buckets[bucket] += 1;
+        }
+    }
+
+    /// Watches a parent chain account, labelled with its role such as `owner`.
+    pub fn watch_operator(&self, role: &str, address: Address) {
+        self.operators
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((role.to_string(), address))
+            .or_default();
+    }
+
+    /// Watches the parent chain account of a private key, such as the batch poster's.
+    pub fn watch_operator_key(&self, role: &str, private_key: &str) -> Result<()> {
+        let signer: PrivateKeySigner = private_key.parse()?;
+        self.watch_operator(role, signer.address());
+        Ok(())
+    }
+
+    /// Counts the restarts made by the stack's supervisor, until it stops.
+    pub fn count_supervisor_restarts(
+        self: &Arc<Self>,
+        mut events: broadcast::Receiver<SupervisorEvent>,
+    ) {
+        let metrics = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(SupervisorEvent::Restarting { service, .. }) => {
+                        *metrics
+                            .supervisor_restarts
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .entry(service)
+                            .or_default() += 1;
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    /// Reads the balances of the operator accounts. Decreases since the last read are summed up
+    /// separately, so top-ups do not offset them. They include gas as well as transfers out.
+    async fn update_operators(&self, parent_chain_rpc: &str) {
+        let accounts: Vec<(String, Address)> = self
+            .operators
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        let provider = get_provider_http(parent_chain_rpc);
+        for (role, address) in accounts {
+            let balance = match provider.get_balance(address).await {
+                Ok(balance) => balance.to_string().parse::<f64>().unwrap_or_default(),
+                Err(e) => {
+                    gadget_sdk::info!("Failed to read the balance of {}: {}", address, e);
+                    continue;
+                }
+            };
+            let mut operators = self.operators.lock().unwrap_or_else(|e| e.into_inner());
+            let operator = operators.entry((role, address)).or_default();
+            if let Some(previous) = operator.balance {
+                operator.decrease += (previous - balance).max(0.0);
+            }
+            operator.balance = Some(balance);
+        }
+    }
+
+    /// Renders all metrics, probing the node stack and the operator balances.
+    pub async fn render(&self, context: &ServiceContext) -> String {
+        let mut out = String::new();
+        self.render_jobs(&mut out);
+        render_stack(&mut out, context, &self.supervisor_restarts).await;
+        render_nitro(&mut out, context).await;
+        if let Ok(parent_chain_rpc) = std::env::var("PARENT_CHAIN_RPC") {
+            self.update_operators(&parent_chain_rpc).await;
+        }
+        self.render_operators(&mut out);
+        out
+    }
+
+    fn render_jobs(&self, out: &mut String) {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        header(
+            out,
+            "orbit_job_duration_seconds",
+            "histogram",
+            "Duration of job runs, by job id and outcome.",
+        );
+        for ((job_id, outcome), stats) in jobs.iter() {
+            let labels = format!("job_id=\"{}\",outcome=\"{}\"", job_id, outcome);
+            let mut cumulated = 0;
+            for (bound, count) in JOB_BUCKETS.iter().zip(stats.buckets) {
+                cumulated += count;
+                let _ = writeln!(
+                    out,
+                    "orbit_job_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulated
+                );
+            }
+            let _ = writeln!(
+                out,
+                "orbit_job_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, stats.count
+            );
+            let _ = writeln!(
+                out,
+                "orbit_job_duration_seconds_sum{{{}}} {}",
+                labels, stats.sum
+            );
+            let _ = writeln!(
+                out,
+                "orbit_job_duration_seconds_count{{{}}} {}",
+                labels, stats.count
+            );
+        }
+    }
+
+    fn render_operators(&self, out: &mut String) {
+        let operators = self.operators.lock().unwrap_or_else(|e| e.into_inner());
+        header(
+            out,
+            "orbit_operator_balance_wei",
+            "gauge",
+            "Balance of an operator account on the parent chain.",
+        );
+        for ((role, address), operator) in operators.iter() {
+            if let Some(balance) = operator.balance {
+                let _ = writeln!(
+                    out,
+                    "orbit_operator_balance_wei{{role=\"{}\",address=\"{}\"}} {}",
+                    role, address, balance
+                );
+            }
+        }
+        header(
+            out,
+            "orbit_operator_balance_decrease_wei_total",
+            "counter",
+            "Decreases of an operator account's balance, from gas and transfers out alike.",
+        );
+        for ((role, address), operator) in operators.iter() {
+            let _ = writeln!(
+                out,
+                "orbit_operator_balance_decrease_wei_total{{role=\"{}\",address=\"{}\"}} {}",
+                role, address, operator.decrease
+            );
+        }
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Renders the state of the stack's services and the heads of its nitro nodes.
+async fn render_stack(
+    out: &mut String,
+    context: &ServiceContext,
+    supervisor_restarts: &Mutex<BTreeMap<String, u64>>,
+) {
+    // Probe a snapshot of the stack, leaving it free for jobs and the supervisor meanwhile
+    let prober = match context.stack.lock().await.as_ref() {
+        Some(stack) => stack.prober(),
+        None => return,
+    };
+    let statuses = match prober.probe_all().await {
+        Ok(statuses) => statuses,
+        Err(e) => {
+            gadget_sdk::info!("Failed to probe the node stack: {}", e);
+            return;
+        }
+    };
+
+    header(
+        out,
+        "orbit_service_up",
+        "gauge",
+        "Whether a service of the stack is in the given health state.",
+    );
+    for status in &statuses {
+        for (health, name) in [
+            (Health::Healthy, "healthy"),
+            (Health::Degraded, "degraded"),
+            (Health::Unhealthy, "unhealthy"),
+            (Health::Unreachable, "unreachable"),
+            (Health::Down, "down"),
+        ] {
+            let _ = writeln!(
+                out,
+                "orbit_service_up{{service=\"{}\",health=\"{}\"}} {}",
+                status.service,
+                name,
+                u8::from(status.health == health)
+            );
+        }
+    }
+    header(
+        out,
+        "orbit_service_container_restarts_total",
+        "counter",
+        "Restarts of a service's container by Docker's restart policy.",
+    );
+    for status in &statuses {
+        let _ = writeln!(
+            out,
+            "orbit_service_container_restarts_total{{service=\"{}\"}} {}",
+            status.service, status.restart_count
+        );
+    }
+    header(
+        out,
+        "orbit_service_supervisor_restarts_total",
+        "counter",
+        "Restarts of a service by the stack's supervisor.",
+    );
+    let restarts = supervisor_restarts
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    for status in &statuses {
+        let _ = writeln!(
+            out,
+            "orbit_service_supervisor_restarts_total{{service=\"{}\"}} {}",
+            status.service,
+            restarts.get(&status.service).copied().unwrap_or_default()
+        );
+    }
+
+    // Lag is measured against the most advanced node of the stack
+    let heads: HashMap<&str, u64> = statuses
+        .iter()
+        .filter_map(|status| Some((status.service.as_str(), status.block_number?)))
+        .collect();
+    let tip = heads.values().copied().max().unwrap_or_default();
+    header(
+        out,
+        "orbit_nitro_head_block",
+        "gauge",
+        "Head block of a nitro node of the stack.",
+    );
+    for (service, head) in &heads {
+        let _ = writeln!(
+            out,
+            "orbit_nitro_head_block{{service=\"{}\"}} {}",
+            service, head
+        );
+    }
+    header(
+        out,
+        "orbit_nitro_head_lag_blocks",
+        "gauge",
+        "Blocks a nitro node is behind the most advanced node of the stack.",
+    );
+    for (service, head) in &heads {
+        let _ = writeln!(
+            out,
+            "orbit_nitro_head_lag_blocks{{service=\"{}\"}} {}",
+            service,
+            tip - head
+        );
+    }
+}
+
+/// A metric family of the nitro nodes: its `# HELP` and `# TYPE` lines, then its samples.
+#[derive(Default)]
+struct Family {
+    help: Option<String>,
+    kind: Option<String>,
+    samples: Vec<String>,
+}
+
+/// Metric families in the order they were first seen.
+#[derive(Default)]
+struct Families {
+    families: Vec<Family>,
+    index: HashMap<String, usize>,
+}
+
+impl Families {
+    fn get(&mut self, name: &str) -> &mut Family {
+        let families = &mut self.families;
+        let index = *self.index.entry(name.to_string()).or_insert_with(|| {
+            families.push(Family::default());
+            families.len() - 1
+        });
+        &mut self.families[index]
+    }
+}
+
+/// Merges the metrics of the stack's nitro nodes, labelling every sample with the chain id and
+/// the service it comes from. A family exposed by several nodes is described once, with the
+/// samples of all of them, as the exposition format requires.
+fn relabel_nitro(chain_id: u64, scrapes: &[(String, String)]) -> String {
+    let mut families = Families::default();
+
+    for (service, body) in scrapes {
+        let labels = format!("chain_id=\"{}\",service=\"{}\"", chain_id, service);
+        // Samples belong to the family described last, such as `_bucket` series of histograms
+        let mut current = String::new();
+        for line in body.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if let Some(comment) = line.strip_prefix('#') {
+                let mut words = comment.split_whitespace();
+                let (Some(keyword), Some(name)) = (words.next(), words.next()) else {
+                    continue;
+                };
+                let family = families.get(name);
+                let meta = match keyword {
+                    "HELP" => &mut family.help,
+                    "TYPE" => &mut family.kind,
+                    _ => continue,
+                };
+                meta.get_or_insert_with(|| line.to_string());
+                current = name.to_string();
+                continue;
+            }
+
+            let name_end = line
+                .find(|c: char| c == '{' || c.is_whitespace())
+                .unwrap_or(line.len());
+            let (name, rest) = line.split_at(name_end);
+            let sample = match rest.strip_prefix('{') {
+                Some(rest) if rest.starts_with('}') => format!("{}{{{}{}", name, labels, rest),
+                Some(rest) => format!("{}{{{},{}", name, labels, rest),
+                None => format!("{}{{{}}}{}", name, labels, rest),
+            };
+            let family = if !current.is_empty() && name.starts_with(current.as_str()) {
+                families.get(&current)
+            } else {
+                families.get(name)
+            };
+            family.samples.push(sample);
+        }
+    }
+
+    let mut out = String::new();
+    for family in families.families {
+        for line in family
+            .help
+            .iter()
+            .chain(&family.kind)
+            .chain(&family.samples)
+        {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    out
+}
+
+/// Renders the metrics of the stack's nitro nodes, relabelled with the chain id so that the
+/// chains of an operator can be told apart. The nodes are scraped from a snapshot of the stack,
+/// so that jobs are not held up by slow scrapes.
+async fn render_nitro(out: &mut String, context: &ServiceContext) {
+    let (chain_id, prober) = {
+        let stack = context.stack.lock().await;
+        let Some(stack) = stack.as_ref() else {
+            return;
+        };
+        (stack.config().chain_id, stack.prober())
+    };
+    match prober.nitro_metrics().await {
+        Ok(scrapes) => out.push_str(&relabel_nitro(chain_id, &scrapes)),
+        Err(e) => gadget_sdk::info!("Failed to scrape the nitro nodes: {}", e),
+    }
+}
+
+/// Answers every request on a connection with the metrics.
+async fn respond(mut stream: TcpStream, metrics: &Metrics, context: &ServiceContext) {
+    // Only the request head matters, and scrapers send no body
+    let mut request = [0u8; 1024];
+    if stream.read(&mut request).await.is_err() {
+        return;
+    }
+    let body = metrics.render(context).await;
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+/// Serves the metrics of the context on `METRICS_ADDR`, or on [`DEFAULT_METRICS_ADDR`].
+pub async fn serve(context: ServiceContext) -> Result<()> {
+    let addr = std::env::var("METRICS_ADDR").unwrap_or_else(|_| DEFAULT_METRICS_ADDR.to_string());
+    let listener = TcpListener::bind(&addr).await?;
+    gadget_sdk::info!("Serving metrics on http://{}/metrics", addr);
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let context = context.clone();
+            tokio::spawn(async move { respond(stream, &context.metrics, &context).await });
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,890 @@
+//! AnyTrust data availability: BLS key generation, keyset encoding and `daserver` containers
+//! for the committee members and the read-only mirror run by this stack.
+
+use crate::events::StackEvent;
+use crate::node_config::{ChainInfo, DasBackend, RestAggregatorSection};
+use crate::{invalid_config, OrbitStack, OrbitStackConfig, NITRO_NODE_IMAGE};
+use alloy_primitives::{Address, Bytes, B256};
+use alloy_sol_types::{sol, SolCall};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::Config,
+    models::{HealthConfig, HostConfig, PortBinding},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Ports of the daserver inside its container
+const DAS_RPC_PORT: u16 = 9876;
+pub(crate) const DAS_REST_PORT: u16 = 9877;
+/// Default host port of the mirror's REST endpoint.
+const DAS_MIRROR_HOST_PORT: u16 = 9875;
+/// Service name of the read-only mirror.
+pub(crate) const DAS_MIRROR_SERVICE: &str = "das-mirror";
+
+// Paths inside the daserver and datool containers
+const DAS_HOME: &str = "/home/user/das";
+const DAS_KEY_DIR: &str = "/home/user/das/keys";
+/// Keys generated for a keyset that is not in use yet.
+const DAS_NEXT_KEY_DIR: &str = "/home/user/das/keys-next";
+const DAS_DATA_DIR: &str = "/home/user/das/data";
+const DAS_CONFIG_DIR: &str = "/home/user/das/config";
+const DAS_SYNC_STATE_DIR: &str = "/home/user/das/sync-state";
+
+/// File under the stack's `das` directory recording the hash of the registered keyset.
+const REGISTERED_KEYSET_FILE: &str = "registered-keyset-hash";
+
+sol! {
+    function setValidKeyset(bytes keysetBytes);
+    function invalidateKeysetHash(bytes32 ksHash);
+}
+
+/// Where a daserver keeps the batch data it stores.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DasStorage {
+    /// One file per batch under the server's data directory.
+    #[default]
+    LocalFile,
+    /// A local badger database under the server's data directory.
+    LocalDb,
+    /// An S3 bucket, which several mirrors can share.
+    S3 {
+        bucket: String,
+        region: String,
+        #[serde(default)]
+        object_prefix: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+/// The AnyTrust committee to run alongside the chain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DasCommitteeConfig {
+    /// Number of committee members run as containers of this stack.
+    pub members: usize,
+    /// Members run elsewhere, with their RPC URL and base64 BLS public key.
+    #[serde(default)]
+    pub external_members: Vec<DasBackend>,
+    /// Number of members assumed to be honest; a batch needs `total - assumed_honest + 1`
+    /// signatures.
+    pub assumed_honest: u64,
+    #[serde(default)]
+    pub storage: DasStorage,
+    /// First host port of the members' endpoints, published on the loopback interface. The
+    /// `index`-th member publishes RPC on `host_port_base + 2 * index` and REST on the port
+    /// after it.
+    #[serde(default = "default_committee_host_port_base")]
+    pub host_port_base: u16,
+}
+
+impl DasCommitteeConfig {
+    pub const DEFAULT_HOST_PORT_BASE: u16 = DAS_RPC_PORT;
+}
+
+fn default_committee_host_port_base() -> u16 {
+    DasCommitteeConfig::DEFAULT_HOST_PORT_BASE
+}
+
+/// A read-only DAS mirror, serving batch data synced from committee REST endpoints without
+/// holding any signing key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DasMirrorConfig {
+    /// REST endpoints to sync from, on top of those of the committee run by this stack.
+    #[serde(default)]
+    pub rest_urls: Vec<String>,
+    /// URL of a list of REST endpoints to sync from, refreshed periodically.
+    #[serde(default)]
+    pub online_url_list: Option<String>,
+    #[serde(default)]
+    pub storage: DasStorage,
+    #[serde(default)]
+    pub sync: DasSyncConfig,
+    /// Host port the mirror's REST endpoint is published on.
+    #[serde(default = "default_mirror_host_port")]
+    pub rest_host_port: u16,
+}
+
+fn default_mirror_host_port() -> u16 {
+    DAS_MIRROR_HOST_PORT
+}
+
+/// How a mirror fills its storage from the parent chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DasSyncConfig {
+    /// Scan the SequencerInbox for batches and fetch their data eagerly, instead of only
+    /// storing what is requested.
+    pub eager: bool,
+    /// Parent chain block to start the eager scan from; defaults to the rollup deployment.
+    pub eager_lower_bound_block: Option<u64>,
+    /// How long to keep synced data, as a Go duration such as `"504h"`.
+    pub retention_period: Option<String>,
+}
+
+/// An encoded committee keyset, ready to be registered on the SequencerInbox.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DasKeyset {
+    pub assumed_honest: u64,
+    pub backends: Vec<DasBackend>,
+    pub keyset: Bytes,
+    pub keyset_hash: B256,
+}
+
+impl DasKeyset {
+    /// Calldata of `SequencerInbox.setValidKeyset` registering this keyset.
+    pub fn set_valid_keyset_calldata(&self) -> Bytes {
+        setValidKeysetCall {
+            keysetBytes: self.keyset.clone(),
+        }
+        .abi_encode()
+        .into()
+    }
+
+    /// Calldata of `SequencerInbox.invalidateKeysetHash` retiring this keyset.
+    pub fn invalidate_keyset_calldata(&self) -> Bytes {
+        invalidate_keyset_calldata(self.keyset_hash)
+    }
+}
+
+/// Calldata of `SequencerInbox.invalidateKeysetHash` retiring the keyset of `keyset_hash`.
+pub fn invalidate_keyset_calldata(keyset_hash: B256) -> Bytes {
+    invalidateKeysetHashCall {
+        ksHash: keyset_hash,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Input of `datool dumpkeyset`.
+#[derive(Serialize)]
+struct KeysetFile {
+    keyset: KeysetSection,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct KeysetSection {
+    assumed_honest: u64,
+    /// JSON-encoded list of [`DasBackend`]s, as in the RPC aggregator's config.
+    backends: String,
+}
+
+/// Renders the `datool dumpkeyset` config of a committee.
+fn render_keyset_file(
+    assumed_honest: u64,
+    backends: &[DasBackend],
+) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&KeysetFile {
+        keyset: KeysetSection {
+            assumed_honest,
+            backends: serde_json::to_string(backends)?,
+        },
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerConfig {
+    pub enable_rpc: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_addr: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_port: Option<u16>,
+    pub enable_rest: bool,
+    pub rest_addr: String,
+    pub rest_port: u16,
+    pub data_availability: DaServerDataAvailability,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerDataAvailability {
+    pub parent_chain_node_url: String,
+    pub sequencer_inbox_address: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<DaServerKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_file_storage: Option<DaServerStorage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_db_storage: Option<DaServerStorage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3_storage: Option<DaServerS3Storage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rest_aggregator: Option<RestAggregatorSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_to_storage: Option<DaServerSyncToStorage>,
+}
+
+impl DaServerDataAvailability {
+    fn new(parent_chain_node_url: String, sequencer_inbox_address: Address) -> Self {
+        Self {
+            parent_chain_node_url,
+            sequencer_inbox_address,
+            key: None,
+            local_file_storage: None,
+            local_db_storage: None,
+            s3_storage: None,
+            rest_aggregator: None,
+            sync_to_storage: None,
+        }
+    }
+
+    fn with_storage(mut self, storage: &DasStorage) -> Self {
+        let local = || DaServerStorage {
+            enable: true,
+            data_dir: DAS_DATA_DIR.to_string(),
+        };
+        match storage {
+            DasStorage::LocalFile => self.local_file_storage = Some(local()),
+            DasStorage::LocalDb => self.local_db_storage = Some(local()),
+            DasStorage::S3 {
+                bucket,
+                region,
+                object_prefix,
+                access_key,
+                secret_key,
+            } => {
+                self.s3_storage = Some(DaServerS3Storage {
+                    enable: true,
+                    bucket: bucket.clone(),
+                    region: region.clone(),
+                    object_prefix: object_prefix.clone(),
+                    access_key: access_key.clone(),
+                    secret_key: secret_key.clone(),
+                })
+            }
+        }
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerKey {
+    pub key_dir: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerStorage {
+    pub enable: bool,
+    pub data_dir: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerS3Storage {
+    pub enable: bool,
+    pub bucket: String,
+    pub region: String,
+    pub object_prefix: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DaServerSyncToStorage {
+    pub eager: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eager_lower_bound_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention_period: Option<String>,
+    pub state_dir: String,
+}
+
+impl OrbitStackConfig {
+    /// Whether the nitro node reads batch data from an AnyTrust committee.
+    pub fn das_enabled(&self) -> bool {
+        self.enable_das || self.das_committee.is_some()
+    }
+
+    pub(crate) fn validate_das(&self) -> Result<(), bollard::errors::Error> {
+        if let Some(committee) = &self.das_committee {
+            check_committee(
+                committee.members + committee.external_members.len(),
+                committee.assumed_honest,
+            )?;
+            if committee.host_port_base as usize + 2 * committee.members > u16::MAX as usize + 1 {
+                return Err(invalid_config(
+                    "the DAS committee's host ports run past 65535",
+                ));
+            }
+        }
+        if let Some(mirror) = &self.das_mirror {
+            let local_members = self
+                .das_committee
+                .as_ref()
+                .map_or(0, |committee| committee.members);
+            if local_members == 0 && mirror.rest_urls.is_empty() && mirror.online_url_list.is_none()
+            {
+                return Err(invalid_config(
+                    "the DAS mirror needs REST endpoints or an online URL list to sync from",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Signer masks are 64 bits wide, and at least one member has to be trusted.
+fn check_committee(size: usize, assumed_honest: u64) -> Result<(), bollard::errors::Error> {
+    if size == 0 || size > 64 {
+        return Err(invalid_config(
+            "the DAS committee must have between 1 and 64 members",
+        ));
+    }
+    if assumed_honest == 0 || assumed_honest > size as u64 {
+        return Err(invalid_config(
+            "assumed_honest must be between 1 and the committee size",
+        ));
+    }
+    Ok(())
+}
+
+/// Service name of the `index`-th committee member.
+pub(crate) fn das_service(index: usize) -> String {
+    format!("das-server-{}", index)
+}
+
+/// RPC endpoint of the `index`-th committee member on the stack's network.
+pub(crate) fn das_rpc_url(index: usize) -> String {
+    format!("http://{}:{}", das_service(index), DAS_RPC_PORT)
+}
+
+/// REST endpoint of the `index`-th committee member on the stack's network.
+pub(crate) fn das_rest_url(index: usize) -> String {
+    format!("http://{}:{}", das_service(index), DAS_REST_PORT)
+}
+
+/// Host ports of the `index`-th committee member's RPC and REST endpoints, counting from `base`.
+pub(crate) fn das_host_ports(base: u16, index: usize) -> (u16, u16) {
+    let rpc_port = base + 2 * index as u16;
+    (rpc_port, rpc_port + 1)
+}
+
+fn json_error(e: serde_json::Error) -> bollard::errors::Error {
+    bollard::errors::Error::IOError {
+        err: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+    }
+}
+
+impl OrbitStack {
+    fn das_member_dir(&self, index: usize) -> PathBuf {
+        self.config.data_dir.join("das").join(das_service(index))
+    }
+
+    /// Number of committee members run by this stack.
+    fn local_das_members(&self) -> usize {
+        self.config
+            .das_committee
+            .as_ref()
+            .map_or(0, |committee| committee.members)
+    }
+
+    /// REST endpoints of the committee members run by this stack, on the stack's network.
+    pub(crate) fn das_rest_urls(&self) -> Vec<String> {
+        (0..self.local_das_members()).map(das_rest_url).collect()
+    }
+
+    /// Generates a BLS keypair for a committee member in `key_dir` unless one is already there,
+    /// and returns its base64 public key. A `fresh` keypair replaces any already there.
+    async fn das_keygen(
+        &self,
+        index: usize,
+        key_dir: &str,
+        fresh: bool,
+    ) -> Result<String, bollard::errors::Error> {
+        let member_dir = self.das_member_dir(index);
+        let relative_key_dir = key_dir.trim_start_matches(DAS_HOME).trim_start_matches('/');
+        let pubkey_path = member_dir.join(relative_key_dir).join("das_bls.pub");
+
+        if fresh || !pubkey_path.exists() {
+            std::fs::create_dir_all(&member_dir)?;
+            let config = Config {
+                image: Some(NITRO_NODE_IMAGE.to_string()),
+                user: Some("root".to_string()),
+                entrypoint: Some(vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    format!(
+                        "rm -rf {0} && datool keygen --dir {0} && mkdir -p {1} && chown -R 1000:1000 {2}",
+                        key_dir, DAS_DATA_DIR, DAS_HOME
+                    ),
+                ]),
+                host_config: Some(HostConfig {
+                    binds: Some(vec![format!("{}:{}", member_dir.display(), DAS_HOME)]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            self.run_to_completion(&format!("{}-keygen", das_service(index)), config)
+                .await?;
+        }
+
+        Ok(std::fs::read_to_string(pubkey_path)?.trim().to_string())
+    }
+
+    /// Encodes the committee keyset with `datool dumpkeyset`, assigning each member its own
+    /// signer bit in order.
+    async fn das_dump_keyset(
+        &self,
+        assumed_honest: u64,
+        mut backends: Vec<DasBackend>,
+    ) -> Result<DasKeyset, bollard::errors::Error> {
+        for (index, backend) in backends.iter_mut().enumerate() {
+            backend.signermask = 1 << index;
+        }
+
+        let das_dir = self.config.data_dir.join("das");
+        std::fs::create_dir_all(&das_dir)?;
+        std::fs::write(
+            das_dir.join("keyset.json"),
+            render_keyset_file(assumed_honest, &backends).map_err(json_error)?,
+        )?;
+
+        let config = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            entrypoint: Some(vec![
+                "datool".to_string(),
+                "dumpkeyset".to_string(),
+                "--conf.file=/keyset/keyset.json".to_string(),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!("{}:/keyset:ro", das_dir.display())]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let output = self.run_to_completion("das-keyset", config).await?;
+
+        // The tool prints `Keyset: 0x...` and `KeysetHash: 0x...`
+        let field = |name: &str| {
+            output
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(name))
+                .map(|value| value.trim().to_string())
+                .ok_or_else(|| invalid_config(&format!("datool output is missing {}", name)))
+        };
+        let keyset = field("Keyset:")?
+            .parse::<Bytes>()
+            .map_err(|e| invalid_config(&e.to_string()))?;
+        let keyset_hash = field("KeysetHash:")?
+            .parse::<B256>()
+            .map_err(|e| invalid_config(&e.to_string()))?;
+
+        Ok(DasKeyset {
+            assumed_honest,
+            backends,
+            keyset,
+            keyset_hash,
+        })
+    }
+
+    /// Renders the daserver configuration of a committee member.
+    pub fn das_server_config(
+        &self,
+        chain_info: &ChainInfo,
+        storage: &DasStorage,
+    ) -> DaServerConfig {
+        DaServerConfig {
+            enable_rpc: true,
+            rpc_addr: Some("0.0.0.0".to_string()),
+            rpc_port: Some(DAS_RPC_PORT),
+            enable_rest: true,
+            rest_addr: "0.0.0.0".to_string(),
+            rest_port: DAS_REST_PORT,
+            data_availability: DaServerDataAvailability {
+                key: Some(DaServerKey {
+                    key_dir: DAS_KEY_DIR.to_string(),
+                }),
+                ..DaServerDataAvailability::new(
+                    self.config.parent_chain_rpc.clone(),
+                    chain_info.rollup.sequencer_inbox,
+                )
+            }
+            .with_storage(storage),
+        }
+    }
+
+    /// Renders the daserver configuration of the mirror: REST only, with no signing key, and
+    /// filled from the committee's REST endpoints.
+    pub fn das_mirror_config(
+        &self,
+        chain_info: &ChainInfo,
+        mirror: &DasMirrorConfig,
+    ) -> DaServerConfig {
+        let mut urls = self.das_rest_urls();
+        urls.extend(mirror.rest_urls.iter().cloned());
+        DaServerConfig {
+            enable_rpc: false,
+            rpc_addr: None,
+            rpc_port: None,
+            enable_rest: true,
+            rest_addr: "0.0.0.0".to_string(),
+            rest_port: DAS_REST_PORT,
+            data_availability: DaServerDataAvailability {
+                rest_aggregator: Some(RestAggregatorSection {
+                    enable: true,
+                    urls,
+                    online_url_list: mirror.online_url_list.clone(),
+                }),
+                sync_to_storage: Some(DaServerSyncToStorage {
+                    eager: mirror.sync.eager,
+                    eager_lower_bound_block: Some(
+                        mirror
+                            .sync
+                            .eager_lower_bound_block
+                            .unwrap_or(chain_info.rollup.deployed_at),
+                    ),
+                    retention_period: mirror.sync.retention_period.clone(),
+                    state_dir: DAS_SYNC_STATE_DIR.to_string(),
+                }),
+                ..DaServerDataAvailability::new(
+                    self.config.parent_chain_rpc.clone(),
+                    chain_info.rollup.sequencer_inbox,
+                )
+            }
+            .with_storage(&mirror.storage),
+        }
+    }
+
+    /// Writes a daserver configuration and starts the server, publishing each
+    /// `(container_port, host_port)` pair on the loopback interface.
+    async fn start_daserver(
+        &mut self,
+        service: &str,
+        service_dir: &Path,
+        das_config: &DaServerConfig,
+        ports: &[(u16, u16)],
+        healthcheck: Option<HealthConfig>,
+    ) -> Result<(), bollard::errors::Error> {
+        std::fs::create_dir_all(service_dir.join("config"))?;
+        std::fs::write(
+            service_dir.join("config").join("daserver.json"),
+            serde_json::to_string_pretty(das_config).map_err(json_error)?,
+        )?;
+
+        let config = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            entrypoint: Some(vec!["daserver".to_string()]),
+            cmd: Some(vec![format!(
+                "--conf.file={}/daserver.json",
+                DAS_CONFIG_DIR
+            )]),
+            exposed_ports: Some(
+                ports
+                    .iter()
+                    .map(|(port, _)| (format!("{}/tcp", port), HashMap::new()))
+                    .collect(),
+            ),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!("{}:{}", service_dir.display(), DAS_HOME)]),
+                port_bindings: Some(
+                    ports
+                        .iter()
+                        .map(|(container_port, host_port)| {
+                            (
+                                format!("{}/tcp", container_port),
+                                Some(vec![PortBinding {
+                                    host_ip: Some("127.0.0.1".to_string()),
+                                    host_port: Some(host_port.to_string()),
+                                }]),
+                            )
+                        })
+                        .collect(),
+                ),
+                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
+                ..Default::default()
+            }),
+            healthcheck,
+            ..Default::default()
+        };
+
+        self.create_and_start_container(service, config).await
+    }
+
+    async fn start_das_server(
+        &mut self,
+        index: usize,
+        das_config: &DaServerConfig,
+    ) -> Result<(), bollard::errors::Error> {
+        let member_dir = self.das_member_dir(index);
+        let base = self
+            .config
+            .das_committee
+            .as_ref()
+            .map_or(DasCommitteeConfig::DEFAULT_HOST_PORT_BASE, |committee| {
+                committee.host_port_base
+            });
+        let (rpc_host_port, rest_host_port) = das_host_ports(base, index);
+        self.start_daserver(
+            &das_service(index),
+            &member_dir,
+            das_config,
+            &[
+                (DAS_RPC_PORT, rpc_host_port),
+                (DAS_REST_PORT, rest_host_port),
+            ],
+            None,
+        )
+        .await
+    }
+
+    /// Generates keys for the local committee members, encodes the keyset and starts a
+    /// daserver per member. The keyset still has to be registered on the SequencerInbox with
+    /// [`DasKeyset::set_valid_keyset_calldata`] unless it is the one recorded as registered,
+    /// see [`OrbitStack::registered_das_keyset_hash`].
+    pub(crate) async fn start_das_committee(&mut self) -> Result<(), bollard::errors::Error> {
+        let Some(committee) = self.config.das_committee.clone() else {
+            return Ok(());
+        };
+        let chain_info = self.config.chain_info()?;
+
+        let mut backends = Vec::with_capacity(committee.members + committee.external_members.len());
+        for index in 0..committee.members {
+            let pubkey = self.das_keygen(index, DAS_KEY_DIR, false).await?;
+            backends.push(DasBackend {
+                url: das_rpc_url(index),
+                pubkey,
+                signermask: 0,
+            });
+        }
+        backends.extend(committee.external_members.iter().cloned());
+
+        let keyset = self
+            .das_dump_keyset(committee.assumed_honest, backends)
+            .await?;
+
+        let das_config = self.das_server_config(&chain_info, &committee.storage);
+        for index in 0..committee.members {
+            self.start_das_server(index, &das_config).await?;
+        }
+
+        self.das_keyset = Some(keyset);
+        Ok(())
+    }
+
+    /// Starts the read-only DAS mirror, if one is configured. Its data directory is handed to
+    /// the image's user first, as for committee members.
+    pub(crate) async fn start_das_mirror(&mut self) -> Result<(), bollard::errors::Error> {
+        let Some(mirror) = self.config.das_mirror.clone() else {
+            return Ok(());
+        };
+        let chain_info = self.config.chain_info()?;
+        let mirror_dir = self.config.data_dir.join("das").join(DAS_MIRROR_SERVICE);
+        std::fs::create_dir_all(&mirror_dir)?;
+
+        let init = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            user: Some("root".to_string()),
+            entrypoint: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "mkdir -p {} {} && chown -R 1000:1000 {}",
+                    DAS_DATA_DIR, DAS_SYNC_STATE_DIR, DAS_HOME
+                ),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!("{}:{}", mirror_dir.display(), DAS_HOME)]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.run_to_completion(&format!("{}-init", DAS_MIRROR_SERVICE), init)
+            .await?;
+
+        let das_config = self.das_mirror_config(&chain_info, &mirror);
+        let healthcheck = HealthConfig {
+            test: Some(vec![
+                "CMD-SHELL".to_string(),
+                format!("curl -sf http://localhost:{}/health", DAS_REST_PORT),
+            ]),
+            interval: Some(10000000000), // 10s in nanoseconds
+            timeout: Some(5000000000),   // 5s in nanoseconds
+            retries: Some(5),
+            start_period: Some(10000000000),
+            ..Default::default()
+        };
+        self.start_daserver(
+            DAS_MIRROR_SERVICE,
+            &mirror_dir,
+            &das_config,
+            &[(DAS_REST_PORT, mirror.rest_host_port)],
+            Some(healthcheck),
+        )
+        .await
+    }
+
+    /// REST endpoint of the mirror run by this stack, on the stack's network.
+    pub(crate) fn das_mirror_url(&self) -> Option<String> {
+        self.config
+            .das_mirror
+            .as_ref()
+            .map(|_| format!("http://{}:{}", DAS_MIRROR_SERVICE, DAS_REST_PORT))
+    }
+
+    /// Keyset of the committee run by this stack, once it has been started.
+    pub fn das_keyset(&self) -> Option<&DasKeyset> {
+        self.das_keyset.as_ref()
+    }
+
+    /// Hash of the keyset last registered on the SequencerInbox for this stack, as recorded in
+    /// its data directory, so that it survives restarts.
+    pub fn registered_das_keyset_hash(&self) -> Result<Option<B256>, bollard::errors::Error> {
+        let path = self
+            .config
+            .data_dir
+            .join("das")
+            .join(REGISTERED_KEYSET_FILE);
+        match std::fs::read_to_string(path) {
+            Ok(hash) => hash
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|e| invalid_config(&format!("invalid registered keyset hash: {}", e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(bollard::errors::Error::IOError { err: e }),
+        }
+    }
+
+    /// Records the keyset of `keyset_hash` as registered on the SequencerInbox.
+    pub fn record_registered_das_keyset(
+        &self,
+        keyset_hash: B256,
+    ) -> Result<(), bollard::errors::Error> {
+        let das_dir = self.config.data_dir.join("das");
+        std::fs::create_dir_all(&das_dir)?;
+        std::fs::write(
+            das_dir.join(REGISTERED_KEYSET_FILE),
+            keyset_hash.to_string(),
+        )?;
+        Ok(())
+    }
+
+    /// Encodes the keyset of a new committee made of the members run by this stack, with fresh
+    /// keys, and `external_members`. The local members keep serving with their current keys
+    /// until the keyset is registered on the SequencerInbox and handed to
+    /// [`OrbitStack::update_das_committee`].
+    pub async fn encode_das_keyset(
+        &self,
+        assumed_honest: u64,
+        external_members: Vec<DasBackend>,
+    ) -> Result<DasKeyset, bollard::errors::Error> {
+        let local_members = self.local_das_members();
+        check_committee(local_members + external_members.len(), assumed_honest)?;
+
+        let mut backends = Vec::with_capacity(local_members + external_members.len());
+        for index in 0..local_members {
+            backends.push(DasBackend {
+                url: das_rpc_url(index),
+                pubkey: self.das_keygen(index, DAS_NEXT_KEY_DIR, true).await?,
+                signermask: 0,
+            });
+        }
+        backends.extend(external_members);
+        self.das_dump_keyset(assumed_honest, backends).await
+    }
+
+    /// Switches the batch poster to a new committee keyset, once registered on the
+    /// SequencerInbox: records it as registered, recreates the local committee members with the
+    /// keys generated for it, points the nitro node at the new DAS REST endpoints and restarts
+    /// it.
+    pub async fn update_das_committee(
+        &mut self,
+        keyset: DasKeyset,
+        das_endpoints: Option<Vec<String>>,
+        das_online_url_list: Option<String>,
+    ) -> Result<(), bollard::errors::Error> {
+        self.record_registered_das_keyset(keyset.keyset_hash)?;
+        if let Some(committee) = self.config.das_committee.clone() {
+            let chain_info = self.config.chain_info()?;
+            let das_config = self.das_server_config(&chain_info, &committee.storage);
+            for index in 0..committee.members {
+                self.promote_das_keys(index).await?;
+                self.remove_container(&das_service(index)).await?;
+                self.emit(StackEvent::ConfigChanged {
+                    service: das_service(index),
+                });
+                self.start_das_server(index, &das_config).await?;
+            }
+        }
+
+        self.das_keyset = Some(keyset);
+        self.config.das_endpoints = das_endpoints;
+        self.config.das_online_url_list = das_online_url_list;
+        self.restart_nitro_node().await
+    }
+
+    /// Replaces the keys of a committee member with those generated for the next keyset, if
+    /// there are any.
+    async fn promote_das_keys(&self, index: usize) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            user: Some("root".to_string()),
+            entrypoint: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "if [ -d {0} ]; then rm -rf {1} && mv {0} {1}; fi",
+                    DAS_NEXT_KEY_DIR, DAS_KEY_DIR
+                ),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}:{}",
+                    self.das_member_dir(index).display(),
+                    DAS_HOME
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.run_to_completion(&format!("{}-keys", das_service(index)), config)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn keyset_backends_are_json_encoded() {
+        let backends = [
+            DasBackend {
+                url: das_rpc_url(0),
+                pubkey: "YAAA".to_string(),
+                signermask: 1,
+            },
+            DasBackend {
+                url: "https://das.example.com".to_string(),
+                pubkey: "YBBB".to_string(),
+                signermask: 2,
+            },
+        ];
+        let rendered: Value =
+            serde_json::from_str(&render_keyset_file(2, &backends).unwrap()).unwrap();
+        assert_eq!(
+            rendered,
+            json!({
+                "keyset": {
+                    "assumed-honest": 2,
+                    "backends": "[{\"url\":\"http://das-server-0:9876\",\"pubkey\":\"YAAA\",\"signermask\":1},\
+                                  {\"url\":\"https://das.example.com\",\"pubkey\":\"YBBB\",\"signermask\":2}]"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn committee_members_are_reached_by_service_name() {
+        assert_eq!(das_rpc_url(1), "http://das-server-1:9876");
+        assert_eq!(das_rest_url(1), "http://das-server-1:9877");
+    }
+}
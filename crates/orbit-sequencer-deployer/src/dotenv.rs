@@ -0,0 +1,411 @@
+//! Parser for `.env` files, following the dotenv format used by docker compose: comments,
+//! `export` prefixes, single and double quotes, and `$VAR`/`${VAR}` expansion.
+
+use std::fmt;
+use std::path::Path;
+
+/// A malformed `.env` file, with the 1-based position of the offending character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DotenvError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DotenvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for DotenvError {}
+
+/// Parses the variables of a `.env` file, in order. A variable defined twice keeps its first
+/// position and its last value. References are expanded from the variables defined above them,
+/// then from the process environment.
+pub fn parse(input: &str) -> Result<Vec<(String, String)>, DotenvError> {
+    Parser::new(input).parse()
+}
+
+/// Reads and parses a `.env` file, reporting parse errors with the file path.
+pub fn from_path(path: &Path) -> Result<Vec<(String, String)>, std::io::Error> {
+    parse(&std::fs::read_to_string(path)?).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), e),
+        )
+    })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    vars: Vec<(String, String)>,
+}
+
+impl Parser {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+            line: 1,
+            column: 1,
+            vars: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> DotenvError {
+        DotenvError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.next();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<(String, String)>, DotenvError> {
+        loop {
+            while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                self.next();
+            }
+            match self.peek() {
+                None => return Ok(self.vars),
+                Some('#') => self.skip_line(),
+                Some(_) => self.parse_assignment()?,
+            }
+        }
+    }
+
+    fn parse_assignment(&mut self) -> Result<(), DotenvError> {
+        let mut key = self.read_name(true)?;
+        if key == "export" && matches!(self.peek(), Some(' ' | '\t')) {
+            self.skip_blanks();
+            key = self.read_name(true)?;
+        }
+
+        self.skip_blanks();
+        if self.peek() != Some('=') {
+            return Err(self.error(format!("expected '=' after {}", key)));
+        }
+        self.next();
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some('\'') => self.parse_single_quoted()?,
+            Some('"') => self.parse_double_quoted()?,
+            _ => self.parse_unquoted()?,
+        };
+        self.end_of_line()?;
+
+        match self.vars.iter_mut().find(|(k, _)| *k == key) {
+            Some(var) => var.1 = value,
+            None => self.vars.push((key, value)),
+        }
+        Ok(())
+    }
+
+    /// Reads a variable name. Keys may also contain `.` and `-`, references may not.
+    fn read_name(&mut self, key: bool) -> Result<String, DotenvError> {
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            let valid = if name.is_empty() {
+                c.is_ascii_alphabetic() || c == '_'
+            } else {
+                c.is_ascii_alphanumeric() || c == '_' || (key && matches!(c, '.' | '-'))
+            };
+            if !valid {
+                break;
+            }
+            name.push(c);
+            self.next();
+        }
+        if name.is_empty() {
+            return Err(self.error("expected a variable name"));
+        }
+        Ok(name)
+    }
+
+    /// After a value, only blanks and a comment may follow on the same line.
+    fn end_of_line(&mut self) -> Result<(), DotenvError> {
+        self.skip_blanks();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.chars.get(self.pos + 1) == Some(&'\n') => Ok(()),
+            Some('#') => {
+                self.skip_line();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("unexpected '{}' after value", c))),
+        }
+    }
+
+    /// Single-quoted values are taken literally and may span lines.
+    fn parse_single_quoted(&mut self) -> Result<String, DotenvError> {
+        let start = self.error("unterminated single-quoted value");
+        self.next();
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                None => return Err(start),
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// Double-quoted values support escapes and expansion, and may span lines.
+    fn parse_double_quoted(&mut self) -> Result<String, DotenvError> {
+        let start = self.error("unterminated double-quoted value");
+        self.next();
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(start),
+                Some('"') => {
+                    self.next();
+                    return Ok(value);
+                }
+                Some('\\') => {
+                    self.next();
+                    match self.next() {
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('t') => value.push('\t'),
+                        Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                        Some(c) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                        None => return Err(start),
+                    }
+                }
+                Some('$') => value.push_str(&self.parse_expansion()?),
+                Some(c) => {
+                    self.next();
+                    value.push(c);
+                }
+            }
+        }
+    }
+
+    /// Unquoted values end at the line end or at a `#` preceded by a blank, and are trimmed.
+    fn parse_unquoted(&mut self) -> Result<String, DotenvError> {
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\n' => break,
+                '\r' if self.chars.get(self.pos + 1) == Some(&'\n') => break,
+                '#' if value.is_empty() || value.ends_with([' ', '\t']) => break,
+                '$' => value.push_str(&self.parse_expansion()?),
+                _ => {
+                    self.next();
+                    value.push(c);
+                }
+            }
+        }
+        Ok(value.trim_end().to_string())
+    }
+
+    /// Expands `$VAR`, `${VAR}` and `${VAR:-default}`. A `$` not followed by a name is kept.
+    fn parse_expansion(&mut self) -> Result<String, DotenvError> {
+        let start = self.error("unterminated variable reference");
+        self.next();
+        match self.peek() {
+            Some('{') => {
+                self.next();
+                let name = self.read_name(false)?;
+                let default = match self.peek() {
+                    Some('}') => {
+                        self.next();
+                        None
+                    }
+                    Some(':') if self.chars.get(self.pos + 1) == Some(&'-') => {
+                        self.next();
+                        self.next();
+                        let mut default = String::new();
+                        loop {
+                            match self.next() {
+                                Some('}') => break,
+                                Some('\n') | None => return Err(start),
+                                Some(c) => default.push(c),
+                            }
+                        }
+                        Some(default)
+                    }
+                    Some('\n') | None => return Err(start),
+                    Some(c) => {
+                        return Err(self.error(format!("unexpected '{}' in variable reference", c)))
+                    }
+                };
+                Ok(match (self.lookup(&name), default) {
+                    (Some(value), _) if !value.is_empty() => value,
+                    (_, Some(default)) => default,
+                    (value, None) => value.unwrap_or_default(),
+                })
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.read_name(false)?;
+                Ok(self.lookup(&name).unwrap_or_default())
+            }
+            _ => Ok("$".to_string()),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.vars
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+            .or_else(|| std::env::var(name).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(input: &str) -> Vec<(String, String)> {
+        parse(input).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_comments_exports_and_unquoted_values() {
+        let input = "# leading comment\n\
+                     export A=1\n\
+                     B = two words   # trailing comment\n\
+                     C=url#fragment\n\
+                     \n\
+                     A=3\n";
+        assert_eq!(
+            vars(input),
+            pairs(&[("A", "3"), ("B", "two words"), ("C", "url#fragment")])
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        let input = r#"SINGLE='literal $A \n # kept'
+DOUBLE="tab\tquote\" backslash\\ dollar\$ other\q"
+MULTI="first
+second"
+"#;
+        assert_eq!(
+            vars(input),
+            pairs(&[
+                ("SINGLE", r"literal $A \n # kept"),
+                ("DOUBLE", "tab\tquote\" backslash\\ dollar$ other\\q"),
+                ("MULTI", "first\nsecond"),
+            ])
+        );
+    }
+
+    #[test]
+    fn expands_references() {
+        let input = r#"A=1
+B=${A}-$A
+C="${A}${ORBIT_DOTENV_TEST_UNSET:-fallback}"
+D=${A:-unused}
+E=${ORBIT_DOTENV_TEST_UNSET}
+F=cost$ 5
+"#;
+        assert_eq!(
+            vars(input),
+            pairs(&[
+                ("A", "1"),
+                ("B", "1-1"),
+                ("C", "1fallback"),
+                ("D", "1"),
+                ("E", ""),
+                ("F", "cost$ 5"),
+            ])
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let error = |input: &str| parse(input).unwrap_err();
+
+        assert_eq!(
+            error("A=1\nB 2\n"),
+            DotenvError {
+                line: 2,
+                column: 3,
+                message: "expected '=' after B".to_string(),
+            }
+        );
+        assert_eq!(
+            error("KEY=${VAR?}\n"),
+            DotenvError {
+                line: 1,
+                column: 10,
+                message: "unexpected '?' in variable reference".to_string(),
+            }
+        );
+        assert_eq!(
+            error("A='x' y\n"),
+            DotenvError {
+                line: 1,
+                column: 7,
+                message: "unexpected 'y' after value".to_string(),
+            }
+        );
+        assert_eq!(
+            error("A=1\nB=\"open\n"),
+            DotenvError {
+                line: 2,
+                column: 3,
+                message: "unterminated double-quoted value".to_string(),
+            }
+        );
+        assert_eq!(
+            error("A=${B\n"),
+            DotenvError {
+                line: 1,
+                column: 3,
+                message: "unterminated variable reference".to_string(),
+            }
+        );
+    }
+}
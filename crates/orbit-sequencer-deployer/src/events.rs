@@ -0,0 +1,199 @@
+//! Lifecycle events of the stack's services. Container events come from Docker, filtered by the
+//! labels the stack puts on its containers; image pulls and configuration changes are emitted
+//! by the stack itself.
+
+use crate::OrbitStack;
+use gadget_sdk::docker::bollard::{
+    self,
+    container::Config,
+    image::CreateImageOptions,
+    models::{EventMessage, EventMessageTypeEnum},
+    system::EventsOptions,
+    Docker,
+};
+use gadget_sdk::info;
+use gadget_sdk::tokio::{self, sync::broadcast};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+
+/// Label naming the stack a container belongs to, by chain name.
+pub(crate) const STACK_LABEL: &str = "orbit.stack";
+/// Label naming the service a container runs.
+pub(crate) const SERVICE_LABEL: &str = "orbit.service";
+
+/// Events buffered for subscribers that fall behind.
+pub(crate) const EVENT_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StackEvent {
+    /// The container of a service is about to be created.
+    Creating {
+        service: String,
+    },
+    Started {
+        service: String,
+    },
+    /// The Docker healthcheck of a service passed.
+    Healthy {
+        service: String,
+    },
+    /// The Docker healthcheck of a service failed.
+    Unhealthy {
+        service: String,
+    },
+    /// The container of a service exited, with its exit code if Docker reported one.
+    Stopped {
+        service: String,
+        exit_code: Option<i64>,
+    },
+    Removed {
+        service: String,
+    },
+    /// An image missing locally was pulled before starting a service.
+    ImagePulled {
+        image: String,
+    },
+    /// A service was recreated to pick up a changed configuration.
+    ConfigChanged {
+        service: String,
+    },
+}
+
+impl StackEvent {
+    /// Maps a Docker container event to the event of the service it concerns.
+    fn from_docker(message: EventMessage) -> Option<Self> {
+        if message.typ != Some(EventMessageTypeEnum::CONTAINER) {
+            return None;
+        }
+        let mut attributes = message.actor?.attributes?;
+        let service = attributes.remove(SERVICE_LABEL)?;
+        Some(match message.action?.as_str() {
+            "start" => Self::Started { service },
+            "health_status: healthy" => Self::Healthy { service },
+            "health_status: unhealthy" => Self::Unhealthy { service },
+            "die" => Self::Stopped {
+                service,
+                exit_code: attributes
+                    .get("exitCode")
+                    .and_then(|code| code.parse().ok()),
+            },
+            "destroy" => Self::Removed { service },
+            _ => return None,
+        })
+    }
+}
+
+/// Forwards the Docker events of the stack's containers until Docker closes the stream.
+async fn watch_docker(docker: Arc<Docker>, stack: String, events: broadcast::Sender<StackEvent>) {
+    let filters = HashMap::from([
+        ("type".to_string(), vec!["container".to_string()]),
+        (
+            "label".to_string(),
+            vec![format!("{}={}", STACK_LABEL, stack)],
+        ),
+    ]);
+    let mut messages = docker.events(Some(EventsOptions {
+        filters,
+        ..Default::default()
+    }));
+    while let Some(message) = messages.next().await {
+        match message {
+            Ok(message) => {
+                if let Some(event) = StackEvent::from_docker(message) {
+                    // Events are dropped when nobody is subscribed
+                    let _ = events.send(event);
+                }
+            }
+            Err(e) => {
+                info!("Docker event stream of {} ended: {}", stack, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Image reference with an explicit tag, as Docker pulls every tag of an untagged image.
+fn tagged(image: &str) -> String {
+    let name = image.rsplit('/').next().unwrap_or(image);
+    if name.contains(':') || name.contains('@') {
+        image.to_string()
+    } else {
+        format!("{}:latest", image)
+    }
+}
+
+impl OrbitStack {
+    /// Receives the lifecycle events of the stack's services from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<StackEvent> {
+        let mut watcher = self.event_watcher.lock().unwrap_or_else(|e| e.into_inner());
+        if watcher.as_ref().map_or(true, |handle| handle.is_finished()) {
+            *watcher = Some(tokio::spawn(watch_docker(
+                Arc::clone(&self.docker),
+                self.config.chain_name.clone(),
+                self.events.clone(),
+            )));
+        }
+        self.events.subscribe()
+    }
+
+    /// Stops forwarding the events of the stack's containers, until the next subscription.
+    pub(crate) fn stop_watching_events(&self) {
+        let mut watcher = self.event_watcher.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(handle) = watcher.take() {
+            handle.abort();
+        }
+    }
+
+    pub(crate) fn emit(&self, event: StackEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Labels a container as a service of this stack.
+    pub(crate) fn label(&self, service: &str, config: &mut Config<String>) {
+        let labels = config.labels.get_or_insert_with(HashMap::new);
+        labels.insert(STACK_LABEL.to_string(), self.config.chain_name.clone());
+        labels.insert(SERVICE_LABEL.to_string(), service.to_string());
+    }
+
+    /// Pulls an image for the given platform unless it is already present.
+    pub(crate) async fn ensure_image(
+        &self,
+        image: &str,
+        platform: &str,
+    ) -> Result<(), bollard::errors::Error> {
+        match self.docker.inspect_image(image).await {
+            Ok(_) => return Ok(()),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            Err(e) => return Err(e),
+        }
+
+        let image = tagged(image);
+        info!("Pulling image {} for {}", image, platform);
+        let mut progress = self.docker.create_image(
+            Some(CreateImageOptions {
+                from_image: image.as_str(),
+                platform,
+                ..Default::default()
+            }),
+            None,
+            None,
+        );
+        while let Some(update) = progress.next().await {
+            update?;
+        }
+        self.emit(StackEvent::ImagePulled { image });
+        Ok(())
+    }
+}
+
+impl Drop for OrbitStack {
+    // The watcher would otherwise outlive the stack, holding on to the Docker event stream
+    fn drop(&mut self) {
+        self.stop_watching_events();
+    }
+}
@@ -0,0 +1,448 @@
+//! Blockscout explorer suite: its databases, backend, frontend and optional microservices,
+//! selected through [`StackProfile`] and [`ExplorerService`].
+
+use crate::{invalid_config, OrbitStack, OrbitStackConfig};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::Config,
+    models::{HostConfig, PortBinding},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Image constants
+const POSTGRES_IMAGE: &str = "postgres:14";
+const REDIS_IMAGE: &str = "redis:alpine";
+const BLOCKSCOUT_IMAGE: &str = "blockscout/blockscout";
+const FRONTEND_IMAGE: &str = "ghcr.io/blockscout/frontend";
+const STATS_IMAGE: &str = "ghcr.io/blockscout/stats";
+const VISUALIZER_IMAGE: &str = "ghcr.io/blockscout/visualizer";
+const SIG_PROVIDER_IMAGE: &str = "ghcr.io/blockscout/sig-provider";
+const SMART_CONTRACT_VERIFIER_IMAGE: &str = "ghcr.io/blockscout/smart-contract-verifier";
+
+/// Tag used for explorer images that are not pinned in [`DockerTags`].
+const DEFAULT_TAG: &str = "latest";
+
+/// Which services run alongside the nitro node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StackProfile {
+    /// Only the nitro node, with its validators and DAS servers.
+    NodeOnly,
+    /// The node plus the Blockscout backend and frontend behind the proxy.
+    NodeExplorer,
+    /// The node, the explorer and every optional explorer service.
+    #[default]
+    Full,
+}
+
+impl FromStr for StackProfile {
+    type Err = bollard::errors::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "node_only" => Ok(Self::NodeOnly),
+            "node_explorer" => Ok(Self::NodeExplorer),
+            "full" => Ok(Self::Full),
+            _ => Err(invalid_config(&format!("unknown stack profile {}", s))),
+        }
+    }
+}
+
+/// Explorer services that can be enabled on their own, on top of the backend and frontend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplorerService {
+    /// Chart statistics, with their own Postgres database.
+    Stats,
+    Visualizer,
+    SigProvider,
+    SmartContractVerifier,
+}
+
+/// Image tags of the explorer services; unset tags default to `latest`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DockerTags {
+    pub blockscout: Option<String>,
+    pub frontend: Option<String>,
+    pub stats: Option<String>,
+    pub visualizer: Option<String>,
+    pub sig_provider: Option<String>,
+    pub smart_contract_verifier: Option<String>,
+}
+
+/// Env files of the explorer services, layered over the env generated from the stack
+/// configuration.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvFiles {
+    pub blockscout: Option<PathBuf>,
+    pub frontend: Option<PathBuf>,
+    pub visualizer: Option<PathBuf>,
+    pub stats: Option<PathBuf>,
+    pub smart_contract_verifier: Option<PathBuf>,
+}
+
+impl OrbitStackConfig {
+    /// Whether the Blockscout backend and frontend run with this stack.
+    pub fn explorer_enabled(&self) -> bool {
+        self.profile != StackProfile::NodeOnly
+    }
+
+    /// Whether an optional explorer service runs with this stack. The profile decides unless
+    /// `explorer_services` lists the services explicitly.
+    pub fn service_enabled(&self, service: ExplorerService) -> bool {
+        match (&self.explorer_services, self.profile) {
+            (_, StackProfile::NodeOnly) => false,
+            (Some(services), _) => services.contains(&service),
+            (None, StackProfile::NodeExplorer) => false,
+            (None, StackProfile::Full) => true,
+        }
+    }
+
+    /// Images of the explorer services that are enabled, by service.
+    pub(crate) fn explorer_images(&self) -> Vec<(&'static str, String)> {
+        if !self.explorer_enabled() {
+            return Vec::new();
+        }
+        let tags = &self.docker_tags;
+        let mut images = vec![
+            ("db", POSTGRES_IMAGE.to_string()),
+            ("redis_db", REDIS_IMAGE.to_string()),
+            ("backend", image(BLOCKSCOUT_IMAGE, &tags.blockscout)),
+            ("frontend", image(FRONTEND_IMAGE, &tags.frontend)),
+        ];
+        if self.service_enabled(ExplorerService::Stats) {
+            images.push(("stats-db", POSTGRES_IMAGE.to_string()));
+            images.push(("stats", image(STATS_IMAGE, &tags.stats)));
+        }
+        if self.service_enabled(ExplorerService::Visualizer) {
+            images.push(("visualizer", image(VISUALIZER_IMAGE, &tags.visualizer)));
+        }
+        if self.service_enabled(ExplorerService::SigProvider) {
+            images.push((
+                "sig-provider",
+                image(SIG_PROVIDER_IMAGE, &tags.sig_provider),
+            ));
+        }
+        if self.service_enabled(ExplorerService::SmartContractVerifier) {
+            images.push((
+                "smart-contract-verifier",
+                image(SMART_CONTRACT_VERIFIER_IMAGE, &tags.smart_contract_verifier),
+            ));
+        }
+        images
+    }
+
+    pub(crate) fn validate_explorer(&self) -> Result<(), bollard::errors::Error> {
+        if !self.explorer_enabled()
+            && self
+                .explorer_services
+                .as_ref()
+                .is_some_and(|services| !services.is_empty())
+        {
+            return Err(invalid_config(
+                "explorer services cannot be enabled with the node_only profile",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn image(image: &str, tag: &Option<String>) -> String {
+    format!("{}:{}", image, tag.as_deref().unwrap_or(DEFAULT_TAG))
+}
+
+impl OrbitStack {
+    /// Starts the explorer databases and Redis, ahead of the nitro node.
+    pub(crate) async fn start_explorer_storage(&mut self) -> Result<(), bollard::errors::Error> {
+        if !self.config.explorer_enabled() {
+            return Ok(());
+        }
+
+        self.config.fill_explorer_secrets()?;
+        self.run_db_init().await?;
+        self.start_db().await?;
+        self.wait_for_healthy("db", Duration::from_secs(60)).await?;
+
+        if self.config.service_enabled(ExplorerService::Stats) {
+            self.run_stats_db_init().await?;
+            self.start_stats_db().await?;
+            self.wait_for_healthy("stats-db", Duration::from_secs(60))
+                .await?;
+        }
+
+        self.start_redis().await
+    }
+
+    /// Starts the explorer services that are enabled.
+    pub(crate) async fn start_explorer(&mut self) -> Result<(), bollard::errors::Error> {
+        if !self.config.explorer_enabled() {
+            return Ok(());
+        }
+
+        self.start_backend().await?;
+        self.start_frontend().await?;
+        if self.config.service_enabled(ExplorerService::Stats) {
+            self.start_stats().await?;
+        }
+        if self.config.service_enabled(ExplorerService::Visualizer) {
+            self.start_visualizer().await?;
+        }
+        if self.config.service_enabled(ExplorerService::SigProvider) {
+            self.start_sig_provider().await?;
+        }
+        if self
+            .config
+            .service_enabled(ExplorerService::SmartContractVerifier)
+        {
+            self.start_smart_contract_verifier().await?;
+        }
+        Ok(())
+    }
+
+    async fn start_redis(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(REDIS_IMAGE.to_string()),
+            cmd: Some(vec!["redis-server".to_string()]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/redis-data:/data",
+                    self.config.data_dir.display()
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("redis_db", config).await
+    }
+
+    /// Hands the data directory of `db` to the user Postgres runs as.
+    async fn run_db_init(&self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(POSTGRES_IMAGE.to_string()),
+            entrypoint: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "chown -R 2000:2000 /var/lib/postgresql/data".to_string(),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/blockscout-db-data:/var/lib/postgresql/data",
+                    self.config.data_dir.display()
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.run_to_completion("db-init", config).await?;
+        Ok(())
+    }
+
+    async fn start_db(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(POSTGRES_IMAGE.to_string()),
+            cmd: Some(vec![
+                "postgres".to_string(),
+                "-c".to_string(),
+                "max_connections=200".to_string(),
+                "-c".to_string(),
+                "client_connection_check_interval=60000".to_string(),
+            ]),
+            env: Some(self.config.explorer.db.postgres_env()),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/blockscout-db-data:/var/lib/postgresql/data",
+                    self.config.data_dir.display()
+                )]),
+                port_bindings: self.config.explorer.db_host_port.map(|port| {
+                    HashMap::from([(
+                        "5432/tcp".to_string(),
+                        Some(vec![PortBinding {
+                            host_ip: Some("127.0.0.1".to_string()),
+                            host_port: Some(port.to_string()),
+                        }]),
+                    )])
+                }),
+                ..Default::default()
+            }),
+            user: Some("2000:2000".to_string()),
+            healthcheck: Some(bollard::models::HealthConfig {
+                test: Some(vec![
+                    "CMD-SHELL".to_string(),
+                    format!(
+                        "pg_isready -U {} -d {}",
+                        self.config.explorer.db.user, self.config.explorer.db.database
+                    ),
+                ]),
+                interval: Some(10000000000), // 10s in nanoseconds
+                timeout: Some(5000000000),   // 5s in nanoseconds
+                retries: Some(5),
+                start_period: Some(10000000000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("db", config).await
+    }
+
+    /// Hands the data directory of `stats-db` to the user Postgres runs as.
+    async fn run_stats_db_init(&self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(POSTGRES_IMAGE.to_string()),
+            entrypoint: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "chown -R 2000:2000 /var/lib/postgresql/data".to_string(),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/stats-db-data:/var/lib/postgresql/data",
+                    self.config.data_dir.display()
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.run_to_completion("stats-db-init", config).await?;
+        Ok(())
+    }
+
+    async fn start_stats_db(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(POSTGRES_IMAGE.to_string()),
+            cmd: Some(vec![
+                "postgres".to_string(),
+                "-c".to_string(),
+                "max_connections=200".to_string(),
+            ]),
+            env: Some(self.config.explorer.stats_db.postgres_env()),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/stats-db-data:/var/lib/postgresql/data",
+                    self.config.data_dir.display()
+                )]),
+                port_bindings: self.config.explorer.stats_db_host_port.map(|port| {
+                    HashMap::from([(
+                        "5432/tcp".to_string(),
+                        Some(vec![PortBinding {
+                            host_ip: Some("127.0.0.1".to_string()),
+                            host_port: Some(port.to_string()),
+                        }]),
+                    )])
+                }),
+                ..Default::default()
+            }),
+            user: Some("2000:2000".to_string()),
+            healthcheck: Some(bollard::models::HealthConfig {
+                test: Some(vec![
+                    "CMD-SHELL".to_string(),
+                    format!(
+                        "pg_isready -U {} -d {}",
+                        self.config.explorer.stats_db.user, self.config.explorer.stats_db.database
+                    ),
+                ]),
+                interval: Some(10000000000),
+                timeout: Some(5000000000),
+                retries: Some(5),
+                start_period: Some(10000000000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("stats-db", config).await
+    }
+
+    async fn start_backend(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(BLOCKSCOUT_IMAGE, &self.config.docker_tags.blockscout)),
+            cmd: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "bin/blockscout eval \"Elixir.Explorer.ReleaseTasks.create_and_migrate()\" && bin/blockscout start".to_string(),
+            ]),
+            env: Some(self.config.blockscout_env()?),
+            host_config: Some(HostConfig {
+                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
+                binds: Some(vec![format!(
+                    "{}/logs:/app/logs",
+                    self.config.data_dir.display()
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("backend", config).await
+    }
+
+    async fn start_frontend(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(FRONTEND_IMAGE, &self.config.docker_tags.frontend)),
+            env: Some(self.config.frontend_env()?),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("frontend", config).await
+    }
+
+    async fn start_stats(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(STATS_IMAGE, &self.config.docker_tags.stats)),
+            env: Some(self.config.stats_env()?),
+            host_config: Some(HostConfig {
+                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("stats", config).await
+    }
+
+    async fn start_visualizer(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(VISUALIZER_IMAGE, &self.config.docker_tags.visualizer)),
+            env: Some(self.config.visualizer_env()?),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("visualizer", config).await
+    }
+
+    async fn start_sig_provider(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(
+                SIG_PROVIDER_IMAGE,
+                &self.config.docker_tags.sig_provider,
+            )),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("sig-provider", config)
+            .await
+    }
+
+    async fn start_smart_contract_verifier(&mut self) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(image(
+                SMART_CONTRACT_VERIFIER_IMAGE,
+                &self.config.docker_tags.smart_contract_verifier,
+            )),
+            env: Some(self.config.smart_contract_verifier_env()?),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("smart-contract-verifier", config)
+            .await
+    }
+}
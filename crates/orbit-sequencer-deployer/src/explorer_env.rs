@@ -0,0 +1,494 @@
+//! Environment of the explorer services, rendered from the stack configuration. Each service's
+//! env is generated first, then the matching entry of [`EnvFiles`] and finally the overrides of
+//! [`ExplorerSettings`] are layered on top.
+
+use crate::explorer::ExplorerService;
+use crate::node_config::DEFAULT_HTTP_PORT;
+use crate::{dotenv, EnvFiles, OrbitStackConfig};
+use alloy_primitives::{hex, B256, B512};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// Default host ports the explorer databases are published on
+const DB_HOST_PORT: u16 = 7432;
+const STATS_DB_HOST_PORT: u16 = 7433;
+/// Port Postgres listens on inside the explorer database containers.
+const POSTGRES_PORT: u16 = 5432;
+/// File under the data directory keeping the secrets generated for the explorer.
+const SECRETS_FILE: &str = "explorer-secrets.json";
+
+/// Settings the explorer environment is rendered from, on top of the chain configuration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplorerSettings {
+    /// Hostname the explorer is served at.
+    pub public_host: String,
+    /// Whether the explorer is served over HTTPS, as when TLS is terminated in front of the
+    /// stack. Implied when the proxy terminates TLS itself.
+    pub https: bool,
+    /// Public RPC URL of the chain shown in the explorer, if it is reachable from outside.
+    pub public_rpc_url: Option<String>,
+    pub currency_name: String,
+    pub currency_symbol: String,
+    pub is_testnet: bool,
+    /// Database credentials. An empty password is generated for the chain.
+    pub db: DbCredentials,
+    pub stats_db: DbCredentials,
+    /// Host ports the databases are published on, on the loopback interface. A database without
+    /// one is only reachable from the stack's network.
+    pub db_host_port: Option<u16>,
+    pub stats_db_host_port: Option<u16>,
+    /// Secret Blockscout signs its sessions with, generated for the chain if empty.
+    pub secret_key_base: String,
+    /// Variables applied last, over the generated env and the env files.
+    pub overrides: EnvOverrides,
+}
+
+impl Default for ExplorerSettings {
+    fn default() -> Self {
+        Self {
+            public_host: "localhost".to_string(),
+            https: false,
+            public_rpc_url: None,
+            currency_name: "Ether".to_string(),
+            currency_symbol: "ETH".to_string(),
+            is_testnet: true,
+            db: DbCredentials {
+                user: "blockscout".to_string(),
+                password: String::new(),
+                database: "blockscout".to_string(),
+            },
+            stats_db: DbCredentials {
+                user: "stats".to_string(),
+                password: String::new(),
+                database: "stats".to_string(),
+            },
+            db_host_port: Some(DB_HOST_PORT),
+            stats_db_host_port: Some(STATS_DB_HOST_PORT),
+            secret_key_base: String::new(),
+            overrides: EnvOverrides::default(),
+        }
+    }
+}
+
+/// Secrets generated for the explorer of a chain, kept under its data directory so that they
+/// stay the same across restarts.
+#[derive(Serialize, Deserialize)]
+struct ExplorerSecrets {
+    db_password: String,
+    stats_db_password: String,
+    secret_key_base: String,
+}
+
+impl ExplorerSecrets {
+    fn generate() -> Self {
+        Self {
+            db_password: hex::encode(B256::random()),
+            stats_db_password: hex::encode(B256::random()),
+            // Phoenix requires at least 64 bytes
+            secret_key_base: hex::encode(B512::random()),
+        }
+    }
+}
+
+impl OrbitStackConfig {
+    /// Fills the explorer secrets left empty with those generated for the chain, generating
+    /// them on first use.
+    pub(crate) fn fill_explorer_secrets(&mut self) -> std::io::Result<()> {
+        let settings = &mut self.explorer;
+        if !settings.db.password.is_empty()
+            && !settings.stats_db.password.is_empty()
+            && !settings.secret_key_base.is_empty()
+        {
+            return Ok(());
+        }
+
+        let path = self.data_dir.join(SECRETS_FILE);
+        let secrets = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).map_err(|e| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let secrets = ExplorerSecrets::generate();
+                std::fs::create_dir_all(&self.data_dir)?;
+                std::fs::write(&path, serde_json::to_string_pretty(&secrets)?)?;
+                secrets
+            }
+            Err(e) => return Err(e),
+        };
+
+        let fill = |value: &mut String, secret: String| {
+            if value.is_empty() {
+                *value = secret;
+            }
+        };
+        fill(&mut settings.db.password, secrets.db_password);
+        fill(&mut settings.stats_db.password, secrets.stats_db_password);
+        fill(&mut settings.secret_key_base, secrets.secret_key_base);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DbCredentials {
+    pub user: String,
+    pub password: String,
+    pub database: String,
+}
+
+impl DbCredentials {
+    /// Connection URL of the database held by the `service` container, on the stack's network.
+    fn url(&self, service: &str) -> String {
+        format!(
+            "postgresql://{}:{}@{}:{}/{}",
+            self.user, self.password, service, POSTGRES_PORT, self.database
+        )
+    }
+
+    /// Variables of the Postgres container holding this database.
+    pub(crate) fn postgres_env(&self) -> Vec<String> {
+        vec![
+            format!("POSTGRES_DB={}", self.database),
+            format!("POSTGRES_USER={}", self.user),
+            format!("POSTGRES_PASSWORD={}", self.password),
+        ]
+    }
+}
+
+/// Per-service variables applied over everything else.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvOverrides {
+    pub blockscout: BTreeMap<String, String>,
+    pub frontend: BTreeMap<String, String>,
+    pub stats: BTreeMap<String, String>,
+    pub visualizer: BTreeMap<String, String>,
+    pub smart_contract_verifier: BTreeMap<String, String>,
+}
+
+/// Blockscout backend settings.
+#[derive(Clone, Debug)]
+pub struct BlockscoutEnv {
+    pub chain_id: u64,
+    pub network: String,
+    pub coin: String,
+    pub coin_name: String,
+    pub rpc_url: String,
+    pub database_url: String,
+    pub redis_url: String,
+    pub secret_key_base: String,
+    pub smart_contract_verifier_url: Option<String>,
+    pub visualizer_url: Option<String>,
+    pub sig_provider_url: Option<String>,
+}
+
+impl BlockscoutEnv {
+    pub fn new(config: &OrbitStackConfig) -> Self {
+        let settings = &config.explorer;
+        let microservice = |service, name: &str| {
+            config
+                .service_enabled(service)
+                .then(|| format!("http://{}:8050/", name))
+        };
+        Self {
+            chain_id: config.chain_id,
+            network: config.chain_name.clone(),
+            coin: settings.currency_symbol.clone(),
+            coin_name: settings.currency_name.clone(),
+            rpc_url: format!("http://nitro-node:{}", DEFAULT_HTTP_PORT),
+            database_url: settings.db.url("db"),
+            redis_url: "redis://redis_db:6379".to_string(),
+            secret_key_base: settings.secret_key_base.clone(),
+            smart_contract_verifier_url: microservice(
+                ExplorerService::SmartContractVerifier,
+                "smart-contract-verifier",
+            ),
+            visualizer_url: microservice(ExplorerService::Visualizer, "visualizer"),
+            sig_provider_url: microservice(ExplorerService::SigProvider, "sig-provider"),
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("CHAIN_TYPE", "arbitrum".to_string()),
+            ("CHAIN_ID", self.chain_id.to_string()),
+            ("NETWORK", self.network.clone()),
+            ("COIN", self.coin.clone()),
+            ("COIN_NAME", self.coin_name.clone()),
+            ("ETHEREUM_JSONRPC_VARIANT", "geth".to_string()),
+            ("ETHEREUM_JSONRPC_HTTP_URL", self.rpc_url.clone()),
+            ("ETHEREUM_JSONRPC_TRACE_URL", self.rpc_url.clone()),
+            ("DATABASE_URL", self.database_url.clone()),
+            ("ECTO_USE_SSL", "false".to_string()),
+            ("ACCOUNT_REDIS_URL", self.redis_url.clone()),
+            ("SECRET_KEY_BASE", self.secret_key_base.clone()),
+            ("PORT", "4000".to_string()),
+            ("API_V2_ENABLED", "true".to_string()),
+            ("DISABLE_EXCHANGE_RATES", "true".to_string()),
+            // Nitro has no public mempool to index
+            (
+                "INDEXER_DISABLE_PENDING_TRANSACTIONS_FETCHER",
+                "true".to_string(),
+            ),
+        ];
+        for (enabled_var, url, url_var) in [
+            (
+                "MICROSERVICE_SC_VERIFIER_ENABLED",
+                &self.smart_contract_verifier_url,
+                "MICROSERVICE_SC_VERIFIER_URL",
+            ),
+            (
+                "MICROSERVICE_VISUALIZE_SOL2UML_ENABLED",
+                &self.visualizer_url,
+                "MICROSERVICE_VISUALIZE_SOL2UML_URL",
+            ),
+            (
+                "MICROSERVICE_SIG_PROVIDER_ENABLED",
+                &self.sig_provider_url,
+                "MICROSERVICE_SIG_PROVIDER_URL",
+            ),
+        ] {
+            vars.push((enabled_var, url.is_some().to_string()));
+            if let Some(url) = url {
+                vars.push((url_var, url.clone()));
+            }
+        }
+        if self.smart_contract_verifier_url.is_some() {
+            vars.push(("MICROSERVICE_SC_VERIFIER_TYPE", "sc_verifier".to_string()));
+        }
+        vars
+    }
+}
+
+/// Blockscout frontend settings.
+#[derive(Clone, Debug)]
+pub struct FrontendEnv {
+    pub public_host: String,
+    pub https: bool,
+    pub network_name: String,
+    pub network_id: u64,
+    pub currency_name: String,
+    pub currency_symbol: String,
+    pub is_testnet: bool,
+    pub public_rpc_url: Option<String>,
+    pub stats_api_url: Option<String>,
+    pub visualizer_api_url: Option<String>,
+}
+
+impl FrontendEnv {
+    pub fn new(config: &OrbitStackConfig) -> Self {
+        let settings = &config.explorer;
+        Self {
+            public_host: settings.public_host.clone(),
+            https: config.public_https(),
+            network_name: config.chain_name.clone(),
+            network_id: config.chain_id,
+            currency_name: settings.currency_name.clone(),
+            currency_symbol: settings.currency_symbol.clone(),
+            is_testnet: settings.is_testnet,
+            public_rpc_url: settings.public_rpc_url.clone(),
+            stats_api_url: config
+                .service_enabled(ExplorerService::Stats)
+                .then(|| config.stats_api_url()),
+            visualizer_api_url: config
+                .service_enabled(ExplorerService::Visualizer)
+                .then(|| config.visualizer_api_url()),
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let protocol = if self.https { "https" } else { "http" };
+        let ws_protocol = if self.https { "wss" } else { "ws" };
+        let mut vars = vec![
+            ("NEXT_PUBLIC_APP_HOST", self.public_host.clone()),
+            ("NEXT_PUBLIC_APP_PROTOCOL", protocol.to_string()),
+            ("NEXT_PUBLIC_API_HOST", self.public_host.clone()),
+            ("NEXT_PUBLIC_API_PROTOCOL", protocol.to_string()),
+            ("NEXT_PUBLIC_API_BASE_PATH", "/".to_string()),
+            (
+                "NEXT_PUBLIC_API_WEBSOCKET_PROTOCOL",
+                ws_protocol.to_string(),
+            ),
+            ("NEXT_PUBLIC_NETWORK_NAME", self.network_name.clone()),
+            ("NEXT_PUBLIC_NETWORK_SHORT_NAME", self.network_name.clone()),
+            ("NEXT_PUBLIC_NETWORK_ID", self.network_id.to_string()),
+            (
+                "NEXT_PUBLIC_NETWORK_CURRENCY_NAME",
+                self.currency_name.clone(),
+            ),
+            (
+                "NEXT_PUBLIC_NETWORK_CURRENCY_SYMBOL",
+                self.currency_symbol.clone(),
+            ),
+            ("NEXT_PUBLIC_NETWORK_CURRENCY_DECIMALS", "18".to_string()),
+            ("NEXT_PUBLIC_IS_TESTNET", self.is_testnet.to_string()),
+        ];
+        if let Some(rpc_url) = &self.public_rpc_url {
+            vars.push(("NEXT_PUBLIC_NETWORK_RPC_URL", rpc_url.clone()));
+        }
+        if let Some(url) = &self.stats_api_url {
+            vars.push(("NEXT_PUBLIC_STATS_API_HOST", url.clone()));
+        }
+        if let Some(url) = &self.visualizer_api_url {
+            vars.push(("NEXT_PUBLIC_VISUALIZE_API_HOST", url.clone()));
+        }
+        vars
+    }
+}
+
+/// Stats service settings.
+#[derive(Clone, Debug)]
+pub struct StatsEnv {
+    pub db_url: String,
+    pub blockscout_db_url: String,
+    pub blockscout_api_url: String,
+}
+
+impl StatsEnv {
+    pub fn new(config: &OrbitStackConfig) -> Self {
+        let settings = &config.explorer;
+        Self {
+            db_url: settings.stats_db.url("stats-db"),
+            blockscout_db_url: settings.db.url("db"),
+            blockscout_api_url: "http://backend:4000".to_string(),
+        }
+    }
+
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("STATS__DB_URL", self.db_url.clone()),
+            ("STATS__BLOCKSCOUT_DB_URL", self.blockscout_db_url.clone()),
+            ("STATS__BLOCKSCOUT_API_URL", self.blockscout_api_url.clone()),
+            ("STATS__CREATE_DATABASE", "true".to_string()),
+            ("STATS__RUN_MIGRATIONS", "true".to_string()),
+        ]
+    }
+}
+
+/// Smart contract verifier settings.
+#[derive(Clone, Debug)]
+pub struct VerifierEnv {
+    pub solidity: bool,
+    pub vyper: bool,
+    pub sourcify: bool,
+}
+
+impl Default for VerifierEnv {
+    fn default() -> Self {
+        Self {
+            solidity: true,
+            vyper: true,
+            sourcify: true,
+        }
+    }
+}
+
+impl VerifierEnv {
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "SMART_CONTRACT_VERIFIER__SERVER__HTTP__ADDR",
+                "0.0.0.0:8050".to_string(),
+            ),
+            (
+                "SMART_CONTRACT_VERIFIER__SOLIDITY__ENABLED",
+                self.solidity.to_string(),
+            ),
+            (
+                "SMART_CONTRACT_VERIFIER__VYPER__ENABLED",
+                self.vyper.to_string(),
+            ),
+            (
+                "SMART_CONTRACT_VERIFIER__SOURCIFY__ENABLED",
+                self.sourcify.to_string(),
+            ),
+        ]
+    }
+}
+
+fn visualizer_vars() -> Vec<(&'static str, String)> {
+    vec![
+        ("VISUALIZER__SERVER__HTTP__ADDR", "0.0.0.0:8050".to_string()),
+        ("VISUALIZER__SERVER__GRPC__ENABLED", "false".to_string()),
+    ]
+}
+
+/// Layers an env file and the overrides over generated variables, later layers winning.
+fn layer(
+    generated: Vec<(&'static str, String)>,
+    env_file: &Option<PathBuf>,
+    overrides: &BTreeMap<String, String>,
+) -> Result<Vec<String>, std::io::Error> {
+    let mut env: BTreeMap<String, String> = generated
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+    if let Some(path) = env_file {
+        env.extend(dotenv::from_path(path)?);
+    }
+    env.extend(overrides.clone());
+    Ok(env
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect())
+}
+
+impl OrbitStackConfig {
+    pub(crate) fn blockscout_env(&self) -> Result<Vec<String>, std::io::Error> {
+        layer(
+            BlockscoutEnv::new(self).vars(),
+            &self.env_files.blockscout,
+            &self.explorer.overrides.blockscout,
+        )
+    }
+
+    pub(crate) fn frontend_env(&self) -> Result<Vec<String>, std::io::Error> {
+        layer(
+            FrontendEnv::new(self).vars(),
+            &self.env_files.frontend,
+            &self.explorer.overrides.frontend,
+        )
+    }
+
+    pub(crate) fn stats_env(&self) -> Result<Vec<String>, std::io::Error> {
+        layer(
+            StatsEnv::new(self).vars(),
+            &self.env_files.stats,
+            &self.explorer.overrides.stats,
+        )
+    }
+
+    pub(crate) fn visualizer_env(&self) -> Result<Vec<String>, std::io::Error> {
+        layer(
+            visualizer_vars(),
+            &self.env_files.visualizer,
+            &self.explorer.overrides.visualizer,
+        )
+    }
+
+    pub(crate) fn smart_contract_verifier_env(&self) -> Result<Vec<String>, std::io::Error> {
+        layer(
+            VerifierEnv::default().vars(),
+            &self.env_files.smart_contract_verifier,
+            &self.explorer.overrides.smart_contract_verifier,
+        )
+    }
+}
+
+impl EnvFiles {
+    /// Env files under `dir`, named after each service, that exist on disk.
+    pub fn existing_in(dir: &std::path::Path) -> Self {
+        let file = |name: &str| Some(dir.join(name)).filter(|path| path.exists());
+        Self {
+            blockscout: file("blockscout.env"),
+            frontend: file("frontend.env"),
+            visualizer: file("visualizer.env"),
+            stats: file("stats.env"),
+            smart_contract_verifier: file("smart-contract-verifier.env"),
+        }
+    }
+}
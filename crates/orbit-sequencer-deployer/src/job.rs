@@ -0,0 +1,156 @@
+//! One-shot job containers, such as the init containers that hand a data directory to the user
+//! of a service's image. Unlike the stack's services they are not tracked: each is run to
+//! completion, its output logged, and it is removed whether it succeeded or not.
+
+use crate::events::{SERVICE_LABEL, STACK_LABEL};
+use crate::OrbitStack;
+use gadget_sdk::docker::bollard::{
+    self,
+    container::{
+        Config, CreateContainerOptions, ListContainersOptions, LogsOptions, RemoveContainerOptions,
+        WaitContainerOptions,
+    },
+};
+use gadget_sdk::info;
+use std::collections::HashMap;
+use tokio_stream::StreamExt;
+
+impl OrbitStack {
+    /// Runs a job container to completion, removes it and returns its output. Fails unless the
+    /// container exits with code 0. A container of the same job of this stack left over from
+    /// an earlier run is removed first.
+    pub(crate) async fn run_to_completion(
+        &self,
+        name: &str,
+        mut config: Config<String>,
+    ) -> Result<String, bollard::errors::Error> {
+        self.label(name, &mut config);
+        let platform = self.config.platform_for(name);
+        if let Some(image) = &config.image {
+            self.ensure_image(image, &platform).await?;
+        }
+        for container_id in self.stale_jobs(name).await? {
+            self.remove_job(&container_id).await?;
+        }
+
+        let container_name = self.config.container_name(name);
+        let container = self
+            .docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: container_name.as_str(),
+                    platform: Some(platform.as_str()),
+                }),
+                config,
+            )
+            .await?;
+        let exit = self.await_job(&container.id).await;
+        let output = self.job_output(&container.id).await;
+        self.remove_job(&container.id).await?;
+        let output = output?;
+
+        for line in output.lines() {
+            info!("[{}] {}", name, line);
+        }
+        match exit? {
+            0 => Ok(output),
+            code => Err(bollard::errors::Error::IOError {
+                err: std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("{} exited with code {}\n{}", name, code, output),
+                ),
+            }),
+        }
+    }
+
+    /// Starts a created job container and waits for it to exit, returning its exit code.
+    async fn await_job(&self, container_id: &str) -> Result<i64, bollard::errors::Error> {
+        self.docker
+            .start_container::<String>(container_id, None)
+            .await?;
+
+        let mut wait = self
+            .docker
+            .wait_container(container_id, None::<WaitContainerOptions<String>>);
+        let mut exit_code = None;
+        while let Some(result) = wait.next().await {
+            match result {
+                Ok(response) => exit_code = Some(response.status_code),
+                // Reported for a non-zero exit code
+                Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => {
+                    exit_code = Some(code)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        exit_code.ok_or_else(|| bollard::errors::Error::IOError {
+            err: std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!("no exit code reported for container {}", container_id),
+            ),
+        })
+    }
+
+    async fn job_output(&self, container_id: &str) -> Result<String, bollard::errors::Error> {
+        Ok(self
+            .docker
+            .logs(
+                container_id,
+                Some(LogsOptions::<String> {
+                    stdout: true,
+                    stderr: true,
+                    ..Default::default()
+                }),
+            )
+            .map(|result| result.map(|log| log.to_string()))
+            .collect::<Result<Vec<_>, _>>()
+            .await?
+            .concat())
+    }
+
+    /// Containers of a job of this stack, found by their labels so that the jobs of other
+    /// stacks are left alone.
+    async fn stale_jobs(&self, name: &str) -> Result<Vec<String>, bollard::errors::Error> {
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![
+                format!("{}={}", STACK_LABEL, self.config.chain_name),
+                format!("{}={}", SERVICE_LABEL, name),
+            ],
+        )]);
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await?;
+        Ok(containers
+            .into_iter()
+            .filter_map(|container| container.id)
+            .collect())
+    }
+
+    /// Removes a job container by id, if it exists.
+    async fn remove_job(&self, container_id: &str) -> Result<(), bollard::errors::Error> {
+        match self
+            .docker
+            .remove_container(
+                container_id,
+                Some(RemoveContainerOptions {
+                    force: true,
+                    v: true,
+                    ..Default::default()
+                }),
+            )
+            .await
+        {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
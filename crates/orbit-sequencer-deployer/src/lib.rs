@@ -1,87 +1,193 @@
 use gadget_sdk::{
     docker::bollard::{
         self,
-        container::{
-            Config, CreateContainerOptions, LogsOptions, RemoveContainerOptions,
-            StopContainerOptions,
-        },
-        models::{HostConfig, PortBinding},
-        network::CreateNetworkOptions,
-        secret::{ContainerStateStatusEnum, HealthStatusEnum},
+        container::{Config, CreateContainerOptions, NetworkingConfig, RemoveContainerOptions},
+        network::{ConnectNetworkOptions, CreateNetworkOptions, DisconnectNetworkOptions},
+        secret::{ContainerStateStatusEnum, EndpointSettings},
         Docker,
     },
-    info, tokio,
+    info,
+    tokio::{self, sync::broadcast, task::JoinHandle},
 };
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
-    hash::Hash,
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tokio_stream::{Stream, StreamExt};
+
+mod das;
+pub mod dotenv;
+mod events;
+mod explorer;
+mod explorer_env;
+mod job;
+mod logs;
+mod nitro;
+pub mod node_config;
+mod platform;
+mod ports;
+mod probe;
+mod proxy;
+mod resources;
+mod shutdown;
+mod supervisor;
+
+pub use das::{
+    invalidate_keyset_calldata, DasCommitteeConfig, DasKeyset, DasMirrorConfig, DasStorage,
+    DasSyncConfig,
+};
+pub use events::StackEvent;
+pub use explorer::{DockerTags, EnvFiles, ExplorerService, StackProfile};
+pub use explorer_env::{
+    BlockscoutEnv, DbCredentials, EnvOverrides, ExplorerSettings, FrontendEnv, StatsEnv,
+    VerifierEnv,
+};
+pub use logs::{LogLine, LogOptions, LogShippingConfig, LogStream};
+pub use nitro::{Endpoint, HostPort, NodeEndpoints, NodeRole};
+use node_config::ChainInfo;
+pub use platform::host_platform;
+pub use probe::{Health, ProbeStatus, Prober};
+pub use proxy::{ProxyConfig, TlsConfig};
+pub use resources::{RestartPolicy, ServiceResources};
+pub use supervisor::{Supervisor, SupervisorConfig, SupervisorEvent, SupervisorState};
 
 // Image constants
 const NITRO_NODE_IMAGE: &str = "offchainlabs/nitro-node:v3.2.1-d81324d";
-const POSTGRES_IMAGE: &str = "postgres:14";
-const REDIS_IMAGE: &str = "redis:alpine";
-const NGINX_IMAGE: &str = "nginx";
-const BLOCKSCOUT_IMAGE: &str = "blockscout/blockscout";
-const FRONTEND_IMAGE: &str = "ghcr.io/blockscout/frontend";
-const STATS_IMAGE: &str = "ghcr.io/blockscout/stats";
-const VISUALIZER_IMAGE: &str = "ghcr.io/blockscout/visualizer";
-const SIG_PROVIDER_IMAGE: &str = "ghcr.io/blockscout/sig-provider";
-const SMART_CONTRACT_VERIFIER_IMAGE: &str = "ghcr.io/blockscout/smart-contract-verifier";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrbitStackConfig {
+    pub parent_chain_id: u64,
     pub parent_chain_rpc: String,
+    /// Beacon API endpoint of the parent chain, used to read blob batches.
+    pub parent_chain_beacon_rpc: Option<String>,
     pub chain_id: u64,
     pub chain_name: String,
     pub chain_info_json: String,
     pub data_dir: PathBuf,
-    pub is_sequencer: bool,
+    pub roles: Vec<NodeRole>,
+    /// Endpoints of the main nitro node and their host bindings.
+    #[serde(default)]
+    pub endpoints: NodeEndpoints,
     pub enable_das: bool,
+    /// Services to run alongside the nitro node.
+    #[serde(default)]
+    pub profile: StackProfile,
+    /// Optional explorer services to run, overriding those implied by the profile.
+    #[serde(default)]
+    pub explorer_services: Option<Vec<ExplorerService>>,
+    #[serde(default)]
     pub docker_tags: DockerTags,
+    #[serde(default)]
     pub env_files: EnvFiles,
+    /// Settings the explorer services' env is generated from.
+    #[serde(default)]
+    pub explorer: ExplorerSettings,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Resource limits and restart policies, by service name or by kind of service such as
+    /// `nitro-validator`, over the stack's defaults.
+    #[serde(default)]
+    pub resources: HashMap<String, ServiceResources>,
+    /// Container platforms such as `linux/arm64`, by service name or kind of service. Services
+    /// without one run on the host's platform.
+    #[serde(default)]
+    pub platforms: HashMap<String, String>,
+    /// How the services are watched and restarted once the stack is up.
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    /// Copying of the services' logs to rotated files under `data_dir/logs`.
+    #[serde(default)]
+    pub log_shipping: LogShippingConfig,
     pub sequencer_endpoint: Option<String>,
     pub das_endpoints: Option<Vec<String>>,
     pub das_online_url_list: Option<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DockerTags {
-    pub blockscout: String,
-    pub frontend: String,
-    pub stats: String,
-    pub visualizer: String,
-    pub sig_provider: String,
-    pub smart_contract_verifier: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct EnvFiles {
-    pub blockscout: PathBuf,
-    pub frontend: PathBuf,
-    pub visualizer: PathBuf,
-    pub stats: PathBuf,
-    pub smart_contract_verifier: PathBuf,
+    /// AnyTrust committee members to run alongside the chain.
+    #[serde(default)]
+    pub das_committee: Option<DasCommitteeConfig>,
+    /// Read-only DAS mirror to run alongside the chain.
+    #[serde(default)]
+    pub das_mirror: Option<DasMirrorConfig>,
+    /// Extra nitro flags, applied on top of the generated config file.
+    #[serde(default)]
+    pub extra_node_args: Vec<String>,
 }
 
 pub struct OrbitStack {
     config: OrbitStackConfig,
     docker: Arc<Docker>,
-    containers: HashMap<String, String>, // service_name -> container_id
+    containers: HashMap<String, String>, // long-running service_name -> container_id
+    das_keyset: Option<DasKeyset>,
+    heads: Arc<Mutex<HashMap<String, (u64, Instant)>>>, // service_name -> (head block, seen since)
+    events: broadcast::Sender<StackEvent>,
+    event_watcher: Mutex<Option<JoinHandle<()>>>,
 }
 
-// Helper function to parse env file
-fn parse_env_file(path: &PathBuf) -> Result<Vec<String>, std::io::Error> {
-    Ok(std::fs::read_to_string(path)?
+/// Id of the container this process runs in, if it runs in one. Docker mounts the container's
+/// hostname file from its own directory of the container, which is named after its id.
+fn own_container_id() -> Option<String> {
+    if !Path::new("/.dockerenv").exists() {
+        return None;
+    }
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo
         .lines()
-        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
-        .map(|line| line.to_string())
-        .collect())
+        .filter(|line| line.contains("/etc/hostname"))
+        .find_map(|line| {
+            let (_, path) = line.split_once("/containers/")?;
+            let id = path.split('/').next()?;
+            (id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit())).then(|| id.to_string())
+        })
+        .or_else(|| std::env::var("HOSTNAME").ok())
+}
+
+// Error for a stack configuration that cannot be started
+fn invalid_config(msg: &str) -> bollard::errors::Error {
+    bollard::errors::Error::IOError {
+        err: std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid stack configuration: {}", msg),
+        ),
+    }
+}
+
+impl OrbitStackConfig {
+    /// Checks that the configuration describes a stack that can be started.
+    pub fn validate(&self) -> Result<(), bollard::errors::Error> {
+        self.validate_roles()?;
+        self.validate_endpoints()?;
+        self.validate_parent_chain()?;
+        self.validate_das()?;
+        self.validate_explorer()?;
+        self.validate_proxy()?;
+        self.validate_host_ports()
+    }
+
+    /// Parses the chain info of the chain this stack runs.
+    pub fn chain_info(&self) -> Result<ChainInfo, bollard::errors::Error> {
+        let infos: Vec<ChainInfo> = serde_json::from_str(&self.chain_info_json).map_err(|e| {
+            bollard::errors::Error::IOError {
+                err: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            }
+        })?;
+        infos
+            .into_iter()
+            .find(|info| info.chain_id == self.chain_id)
+            .ok_or_else(|| invalid_config("chain_info_json does not describe chain_id"))
+    }
+
+    /// Bridge network shared by the stack's services, which reach each other by service name.
+    /// Each chain gets its own, keeping the stacks of a host apart.
+    pub fn network_name(&self) -> String {
+        format!("{}_network", self.chain_name)
+    }
+
+    /// Name of the container of a service or job. Services are reached by their bare name on
+    /// the stack's network, which is an alias of their container there.
+    pub fn container_name(&self, service: &str) -> String {
+        format!("{}-{}", self.chain_name, service)
+    }
 }
 
 impl OrbitStack {
@@ -90,23 +196,65 @@ impl OrbitStack {
             config,
             docker,
             containers: HashMap::new(),
+            das_keyset: None,
+            heads: Arc::new(Mutex::new(HashMap::new())),
+            events: broadcast::channel(events::EVENT_CAPACITY).0,
+            event_watcher: Mutex::new(None),
+        }
+    }
+
+    pub fn config(&self) -> &OrbitStackConfig {
+        &self.config
+    }
+
+    /// HTTP RPC endpoint of the nitro node as published on the host, or as seen from the stack's
+    /// network when it is not published.
+    pub fn rpc_endpoint(&self) -> String {
+        match &self.config.endpoints.http.publish {
+            Some(publish) if publish.host_ip == "0.0.0.0" => {
+                format!("http://127.0.0.1:{}", publish.host_port)
+            }
+            Some(publish) => format!("http://{}:{}", publish.host_ip, publish.host_port),
+            None => format!("http://nitro-node:{}", node_config::DEFAULT_HTTP_PORT),
         }
     }
 
-    async fn create_and_start_container<T>(
+    async fn create_and_start_container(
         &mut self,
         name: &str,
-        config: Config<T>,
-    ) -> Result<(), bollard::errors::Error>
-    where
-        T: Into<String> + Eq + Hash + Serialize,
-    {
+        mut config: Config<String>,
+    ) -> Result<(), bollard::errors::Error> {
+        self.label(name, &mut config);
+        let network_name = self.config.network_name();
+        let host_config = config.host_config.get_or_insert_with(Default::default);
+        host_config
+            .network_mode
+            .get_or_insert_with(|| network_name.clone());
+        self.config.service_resources(name).apply(host_config);
+        config.networking_config = Some(NetworkingConfig {
+            endpoints_config: HashMap::from([(
+                network_name,
+                EndpointSettings {
+                    aliases: Some(vec![name.to_string()]),
+                    ..Default::default()
+                },
+            )]),
+        });
+
+        let platform = self.config.platform_for(name);
+        if let Some(image) = &config.image {
+            self.ensure_image(image, &platform).await?;
+        }
+        self.emit(StackEvent::Creating {
+            service: name.to_string(),
+        });
+        let container_name = self.config.container_name(name);
         let container = self
             .docker
             .create_container(
                 Some(CreateContainerOptions {
-                    name,
-                    platform: Some("linux/amd64"),
+                    name: container_name.as_str(),
+                    platform: Some(platform.as_str()),
                 }),
                 config,
             )
@@ -115,423 +263,11 @@ impl OrbitStack {
         self.docker
             .start_container::<String>(&container.id, None)
             .await?;
+        self.ship_service_logs(name, &container.id);
         self.containers.insert(name.to_string(), container.id);
         Ok(())
     }
 
-    async fn start_nitro_node(&mut self) -> Result<(), bollard::errors::Error> {
-        let mut cmd_args = vec![
-            format!(
-                "--parent-chain.connection.url={}",
-                self.config.parent_chain_rpc
-            ),
-            format!("--chain.id={}", self.config.chain_id),
-            format!("--chain.name={}", self.config.chain_name),
-            format!("--chain.info-json={}", self.config.chain_info_json),
-            "--http.api=net,web3,eth".to_string(),
-            "--http.corsdomain=*".to_string(),
-            "--http.addr=0.0.0.0".to_string(),
-            "--http.vhosts=*".to_string(),
-            "--http.port=8449".to_string(), // Add explicit HTTP port
-        ];
-
-        // Add sequencer-specific configuration
-        if !self.config.is_sequencer {
-            if let Some(endpoint) = &self.config.sequencer_endpoint {
-                cmd_args.push(format!("--execution.forwarding-target={}", endpoint));
-            }
-        } else {
-            cmd_args.extend_from_slice(&[
-                "--node.feed.output.enable=true".to_string(),
-                "--node.feed.output.addr=0.0.0.0".to_string(),
-                "--node.feed.output.port=9642".to_string(),
-            ]);
-        }
-
-        // Add DAS configuration if enabled
-        if self.config.enable_das {
-            cmd_args.push("--node.data-availability.enable".to_string());
-            if let Some(endpoints) = &self.config.das_endpoints {
-                cmd_args.push(format!(
-                    "--node.data-availability.rest-aggregator.urls={}",
-                    endpoints.join(",")
-                ));
-            }
-            if let Some(url) = &self.config.das_online_url_list {
-                cmd_args.push(format!(
-                    "--node.data-availability.rest-aggregator.online-url-list={}",
-                    url
-                ));
-            }
-        }
-
-        let config = Config {
-            image: Some(NITRO_NODE_IMAGE),
-            exposed_ports: Some({
-                let mut ports = HashMap::new();
-                ports.insert("8449/tcp", HashMap::new());
-                ports
-            }),
-            host_config: Some(HostConfig {
-                port_bindings: Some({
-                    let mut bindings = HashMap::new();
-                    bindings.insert(
-                        "8449/tcp".to_string(),
-                        Some(vec![PortBinding {
-                            host_ip: Some("127.0.0.1".to_string()), // Match docker-compose binding
-                            host_port: Some("8449".to_string()),
-                        }]),
-                    );
-                    bindings
-                }),
-                ..Default::default()
-            }),
-            cmd: Some(cmd_args.iter().map(|s| s.as_str()).collect()),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("nitro-node", config).await
-    }
-    async fn start_das_server(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(NITRO_NODE_IMAGE.to_string()),
-            entrypoint: Some(vec!["/bin/bash".to_string(), "/das-server.sh".to_string()]),
-            volumes: Some({
-                let mut volumes = HashMap::new();
-                volumes.insert(
-                    format!(
-                        "{}/das-server.sh:/das-server.sh",
-                        self.config.data_dir.display()
-                    ),
-                    HashMap::new(),
-                );
-                volumes.insert(
-                    format!(
-                        "{}/das-data:/home/user/das-data",
-                        self.config.data_dir.display()
-                    ),
-                    HashMap::new(),
-                );
-                volumes
-            }),
-            exposed_ports: Some({
-                let mut ports = HashMap::new();
-                for port in ["9876", "9877"] {
-                    ports.insert(format!("{}/tcp", port), HashMap::new());
-                }
-                ports
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("das-server", config).await
-    }
-
-    // Implementation of individual service starts
-    async fn start_redis(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(REDIS_IMAGE.to_string()),
-            cmd: Some(vec!["redis-server".to_string()]),
-            host_config: Some(HostConfig {
-                binds: Some(vec![format!(
-                    "{}/redis-data:/data",
-                    self.config.data_dir.display()
-                )]),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("redis_db", config).await
-    }
-
-    async fn start_db_init(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(POSTGRES_IMAGE.to_string()),
-            entrypoint: Some(vec![
-                "sh".to_string(),
-                "-c".to_string(),
-                "chown -R 2000:2000 /var/lib/postgresql/data".to_string(),
-            ]),
-            host_config: Some(HostConfig {
-                binds: Some(vec![format!(
-                    "{}/blockscout-db-data:/var/lib/postgresql/data",
-                    self.config.data_dir.display()
-                )]),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("db-init", config).await
-    }
-
-    async fn start_db(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(POSTGRES_IMAGE.to_string()),
-            cmd: Some(vec![
-                "postgres".to_string(),
-                "-c".to_string(),
-                "max_connections=200".to_string(),
-                "-c".to_string(),
-                "client_connection_check_interval=60000".to_string(),
-            ]),
-            env: Some(vec![
-                "POSTGRES_DB=blockscout".to_string(),
-                "POSTGRES_USER=blockscout".to_string(),
-                "POSTGRES_PASSWORD=ceWb1MeLBEeOIfk65gU8EjF8".to_string(),
-            ]),
-            host_config: Some(HostConfig {
-                binds: Some(vec![format!(
-                    "{}/blockscout-db-data:/var/lib/postgresql/data",
-                    self.config.data_dir.display()
-                )]),
-                port_bindings: Some({
-                    let mut bindings = HashMap::new();
-                    bindings.insert(
-                        "5432/tcp".to_string(),
-                        Some(vec![PortBinding {
-                            host_ip: Some("0.0.0.0".to_string()),
-                            host_port: Some("7432".to_string()),
-                        }]),
-                    );
-                    bindings
-                }),
-                ..Default::default()
-            }),
-            user: Some("2000:2000".to_string()),
-            healthcheck: Some(bollard::models::HealthConfig {
-                test: Some(vec![
-                    "CMD-SHELL".to_string(),
-                    "pg_isready -U blockscout -d blockscout".to_string(),
-                ]),
-                interval: Some(10000000000), // 10s in nanoseconds
-                timeout: Some(5000000000),   // 5s in nanoseconds
-                retries: Some(5),
-                start_period: Some(10000000000),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("db", config).await
-    }
-
-    async fn start_stats_db_init(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(POSTGRES_IMAGE.to_string()),
-            entrypoint: Some(vec![
-                "sh".to_string(),
-                "-c".to_string(),
-                "chown -R 2000:2000 /var/lib/postgresql/data".to_string(),
-            ]),
-            host_config: Some(HostConfig {
-                binds: Some(vec![format!(
-                    "{}/stats-db-data:/var/lib/postgresql/data",
-                    self.config.data_dir.display()
-                )]),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("stats-db-init", config)
-            .await
-    }
-
-    async fn start_stats_db(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(POSTGRES_IMAGE.to_string()),
-            cmd: Some(vec![
-                "postgres".to_string(),
-                "-c".to_string(),
-                "max_connections=200".to_string(),
-            ]),
-            env: Some(vec![
-                "POSTGRES_DB=stats".to_string(),
-                "POSTGRES_USER=stats".to_string(),
-                "POSTGRES_PASSWORD=n0uejXPl61ci6ldCuE2gQU5Y".to_string(),
-            ]),
-            host_config: Some(HostConfig {
-                binds: Some(vec![format!(
-                    "{}/stats-db-data:/var/lib/postgresql/data",
-                    self.config.data_dir.display()
-                )]),
-                port_bindings: Some({
-                    let mut bindings = HashMap::new();
-                    bindings.insert(
-                        "5432/tcp".to_string(),
-                        Some(vec![PortBinding {
-                            host_ip: Some("0.0.0.0".to_string()),
-                            host_port: Some("7433".to_string()),
-                        }]),
-                    );
-                    bindings
-                }),
-                ..Default::default()
-            }),
-            user: Some("2000:2000".to_string()),
-            healthcheck: Some(bollard::models::HealthConfig {
-                test: Some(vec![
-                    "CMD-SHELL".to_string(),
-                    "pg_isready -U stats -d stats".to_string(),
-                ]),
-                interval: Some(10000000000),
-                timeout: Some(5000000000),
-                retries: Some(5),
-                start_period: Some(10000000000),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("stats-db", config).await
-    }
-
-    async fn start_backend(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!("{}:{}", BLOCKSCOUT_IMAGE, self.config.docker_tags.blockscout)),
-            cmd: Some(vec![
-                "sh".to_string(),
-                "-c".to_string(),
-                "bin/blockscout eval \"Elixir.Explorer.ReleaseTasks.create_and_migrate()\" && bin/blockscout start".to_string(),
-            ]),
-            env: Some(parse_env_file(&self.config.env_files.blockscout)?),
-            host_config: Some(HostConfig {
-                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
-                binds: Some(vec![format!(
-                    "{}/logs:/app/logs",
-                    self.config.data_dir.display()
-                )]),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("backend", config).await
-    }
-
-    async fn start_frontend(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!(
-                "{}:{}",
-                FRONTEND_IMAGE, self.config.docker_tags.frontend
-            )),
-            env: Some(parse_env_file(&self.config.env_files.frontend)?),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("frontend", config).await
-    }
-
-    async fn start_stats(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!("{}:{}", STATS_IMAGE, self.config.docker_tags.stats)),
-            env: Some(
-                std::fs::read_to_string(&self.config.env_files.stats)?
-                    .lines()
-                    .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
-                    .map(|line| line.to_string())
-                    .collect::<Vec<String>>(),
-            ),
-            host_config: Some(HostConfig {
-                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("stats", config).await
-    }
-
-    async fn start_visualizer(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!(
-                "{}:{}",
-                VISUALIZER_IMAGE, self.config.docker_tags.visualizer
-            )),
-            env: Some(parse_env_file(&self.config.env_files.visualizer)?),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("visualizer", config).await
-    }
-
-    async fn start_sig_provider(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!(
-                "{}:{}",
-                SIG_PROVIDER_IMAGE, self.config.docker_tags.sig_provider
-            )),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("sig-provider", config)
-            .await
-    }
-
-    async fn start_smart_contract_verifier(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(format!(
-                "{}:{}",
-                SMART_CONTRACT_VERIFIER_IMAGE, self.config.docker_tags.smart_contract_verifier
-            )),
-            env: Some(parse_env_file(
-                &self.config.env_files.smart_contract_verifier,
-            )?),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("smart-contract-verifier", config)
-            .await
-    }
-
-    async fn start_proxy(&mut self) -> Result<(), bollard::errors::Error> {
-        let config = Config {
-            image: Some(NGINX_IMAGE.to_string()),
-            host_config: Some(HostConfig {
-                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
-                binds: Some(vec![format!(
-                    "{}/proxy:/etc/nginx/templates",
-                    self.config.data_dir.display()
-                )]),
-                port_bindings: Some({
-                    let mut bindings = HashMap::new();
-                    for (container_port, host_port) in
-                        [("80/tcp", "80"), ("8080/tcp", "8080"), ("8081/tcp", "8081")]
-                    {
-                        bindings.insert(
-                            container_port.to_string(),
-                            Some(vec![PortBinding {
-                                host_ip: Some("0.0.0.0".to_string()),
-                                host_port: Some(host_port.to_string()),
-                            }]),
-                        );
-                    }
-                    bindings
-                }),
-                ..Default::default()
-            }),
-            env: Some(vec![
-                "BACK_PROXY_PASS=http://backend:4000".to_string(),
-                "FRONT_PROXY_PASS=http://frontend:3000".to_string(),
-            ]),
-            ..Default::default()
-        };
-
-        self.create_and_start_container("proxy", config).await
-    }
-
-    pub async fn stop_all(&mut self) -> Result<(), bollard::errors::Error> {
-        for (service, container_id) in &self.containers {
-            info!("Stopping container for service: {}", service);
-            self.docker
-                .stop_container(container_id, Some(StopContainerOptions { t: 300 }))
-                .await?;
-        }
-        Ok(())
-    }
-
     pub async fn restart_all(&mut self) -> Result<(), bollard::errors::Error> {
         for (service, container_id) in &self.containers {
             info!("Restarting container for service: {}", service);
@@ -540,38 +276,9 @@ impl OrbitStack {
         Ok(())
     }
 
-    pub async fn get_logs(
-        &self,
-        service: &str,
-    ) -> Option<impl Stream<Item = Result<String, bollard::errors::Error>>> {
-        self.containers.get(service).map(|container_id| {
-            let options = LogsOptions::<String> {
-                stdout: true,
-                stderr: true,
-                follow: true,
-                timestamps: true,
-                tail: "100".to_string(),
-                ..Default::default()
-            };
-
-            Box::pin(
-                self.docker
-                    .logs(container_id, Some(options))
-                    .map(|result| result.map(|log| log.to_string())),
-            )
-        })
-    }
-
+    /// Whether a service passes its probe and is up to date. See [`OrbitStack::probe`].
     pub async fn is_healthy(&self, service: &str) -> Result<bool, bollard::errors::Error> {
-        if let Some(container_id) = self.containers.get(service) {
-            let inspect = self.docker.inspect_container(container_id, None).await?;
-            if let Some(state) = inspect.state {
-                return Ok(state.health.map_or(state.running.unwrap_or(false), |h| {
-                    h.status.map_or(false, |s| s == HealthStatusEnum::HEALTHY)
-                }));
-            }
-        }
-        Ok(false)
+        Ok(self.probe(service).await?.is_healthy())
     }
 
     pub async fn wait_for_healthy(
@@ -612,14 +319,6 @@ impl OrbitStack {
         Ok(())
     }
 
-    pub async fn cleanup(&mut self) -> Result<(), bollard::errors::Error> {
-        self.stop_all().await?;
-        for service in self.containers.keys().cloned().collect::<Vec<_>>() {
-            self.remove_container(&service).await?;
-        }
-        Ok(())
-    }
-
     pub async fn get_container_status(
         &self,
         service: &str,
@@ -635,56 +334,110 @@ impl OrbitStack {
 
     async fn ensure_network(&self) -> Result<(), bollard::errors::Error> {
         let networks = self.docker.list_networks::<String>(None).await?;
-        let network_name = "orbit_network";
+        let network_name = self.config.network_name();
 
         if !networks
             .iter()
-            .any(|n| n.name.as_deref() == Some(network_name))
+            .any(|n| n.name.as_deref() == Some(network_name.as_str()))
         {
             self.docker
                 .create_network(CreateNetworkOptions {
-                    name: network_name.to_string(),
+                    name: network_name,
                     driver: "bridge".to_string(),
                     ..Default::default()
                 })
                 .await?;
         }
+        self.join_network().await
+    }
+
+    /// Attaches the container this process runs in, if any, to the stack's network, so that the
+    /// services can be probed on their addresses there. Those are only routable from the host
+    /// itself otherwise.
+    async fn join_network(&self) -> Result<(), bollard::errors::Error> {
+        let Some(container) = own_container_id() else {
+            return Ok(());
+        };
+        let network_name = self.config.network_name();
+        let inspect = self.docker.inspect_container(&container, None).await?;
+        let network_mode = inspect
+            .host_config
+            .and_then(|host_config| host_config.network_mode);
+        // A container on the host's network reaches the stack's addresses like the host does
+        if network_mode.as_deref() == Some("host") {
+            return Ok(());
+        }
+        let container = inspect.id.unwrap_or(container);
+        let joined = self
+            .docker
+            .inspect_network::<String>(&network_name, None)
+            .await?
+            .containers
+            .is_some_and(|containers| containers.contains_key(&container));
+        if !joined {
+            info!("Attaching container {} to {}", container, network_name);
+            self.docker
+                .connect_network(
+                    &network_name,
+                    ConnectNetworkOptions {
+                        container: container.as_str(),
+                        endpoint_config: EndpointSettings::default(),
+                    },
+                )
+                .await?;
+        }
         Ok(())
     }
 
+    /// Detaches the container this process runs in, if any, from the stack's network, which
+    /// cannot be removed while it is attached.
+    async fn leave_network(&self) -> Result<(), bollard::errors::Error> {
+        let Some(container) = own_container_id() else {
+            return Ok(());
+        };
+        match self
+            .docker
+            .disconnect_network(
+                &self.config.network_name(),
+                DisconnectNetworkOptions {
+                    container: container.as_str(),
+                    force: true,
+                },
+            )
+            .await
+        {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 403 | 404,
+                ..
+            }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn start_with_dependencies(&mut self) -> Result<(), bollard::errors::Error> {
+        self.config.validate()?;
+        self.check_platforms().await?;
+        self.check_host_ports().await?;
         self.ensure_network().await?;
 
         // Start databases first
-        self.start_db_init().await?;
-        self.start_db().await?;
-        self.wait_for_healthy("db", Duration::from_secs(60)).await?;
-
-        self.start_stats_db_init().await?;
-        self.start_stats_db().await?;
-        self.wait_for_healthy("stats-db", Duration::from_secs(60))
-            .await?;
+        self.start_explorer_storage().await?;
 
-        // Start Redis
-        self.start_redis().await?;
-
-        // Start nitro node and DAS server if enabled
-        self.start_nitro_node().await?;
-        if self.config.enable_das {
-            self.start_das_server().await?;
+        // Start the DAS committee first so the node can store batches with it
+        self.start_das_committee().await?;
+        if self.config.das_mirror.is_some() {
+            self.start_das_mirror().await?;
+            self.wait_for_healthy(das::DAS_MIRROR_SERVICE, Duration::from_secs(60))
+                .await?;
         }
+        self.start_nitro_node().await?;
+        self.start_validators().await?;
 
         // Start main services
-        self.start_backend().await?;
-        self.start_frontend().await?;
-        self.start_stats().await?;
-        self.start_visualizer().await?;
-        self.start_sig_provider().await?;
-        self.start_smart_contract_verifier().await?;
+        self.start_explorer().await?;
 
         // Start proxy last
-        self.start_proxy().await?;
-
-        Ok(())
+        self.start_proxy().await
     }
 }
@@ -0,0 +1,405 @@
+//! Retrieval of the services' container logs, streamed or collected, bounded by time and
+//! count and split by output stream, and their shipping to rotated files that outlive the
+//! containers.
+
+use crate::OrbitStack;
+use flate2::{write::GzEncoder, Compression};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::{LogOutput, LogsOptions},
+    Docker,
+};
+use gadget_sdk::{info, tokio};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_stream::{Stream, StreamExt};
+
+/// Wait before following the logs of a service again once its container stopped.
+const RESUME_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shipping of the services' logs to `data_dir/logs/<service>`. The current file of a service
+/// is rotated once it reaches `max_file_bytes` or `max_file_age_secs`, and only the newest
+/// `max_files` rotated files are kept.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogShippingConfig {
+    pub enable: bool,
+    pub max_file_bytes: u64,
+    pub max_file_age_secs: u64,
+    pub max_files: usize,
+    /// Gzips rotated files.
+    pub compress: bool,
+}
+
+impl Default for LogShippingConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            max_file_bytes: 64 << 20,
+            max_file_age_secs: 24 * 60 * 60,
+            max_files: 10,
+            compress: true,
+        }
+    }
+}
+
+/// Which logs of a service to retrieve.
+#[derive(Clone, Debug)]
+pub struct LogOptions {
+    /// Only lines logged at or after this time.
+    pub since: Option<SystemTime>,
+    /// Only lines logged before this time.
+    pub until: Option<SystemTime>,
+    /// Only the last lines, before following.
+    pub tail: Option<usize>,
+    /// Keeps the stream open for new lines once the existing ones are read.
+    pub follow: bool,
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            since: None,
+            until: None,
+            tail: None,
+            follow: false,
+            stdout: true,
+            stderr: true,
+        }
+    }
+}
+
+impl LogOptions {
+    /// The last `tail` lines, then new lines as they are logged.
+    pub fn follow(tail: usize) -> Self {
+        Self {
+            tail: Some(tail),
+            follow: true,
+            ..Default::default()
+        }
+    }
+
+    fn to_docker(&self) -> LogsOptions<String> {
+        let unix = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64)
+        };
+        LogsOptions {
+            follow: self.follow,
+            stdout: self.stdout,
+            stderr: self.stderr,
+            since: self.since.map_or(0, unix),
+            until: self.until.map_or(0, unix),
+            timestamps: true,
+            tail: self
+                .tail
+                .map_or_else(|| "all".to_string(), |tail| tail.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Output of a container with a TTY, where both streams are merged.
+    Console,
+}
+
+/// A line logged by a service.
+#[derive(Clone, Debug, Serialize)]
+pub struct LogLine {
+    pub service: String,
+    pub stream: LogStream,
+    /// Time Docker received the line, if it could be parsed.
+    pub timestamp: Option<SystemTime>,
+    pub message: String,
+}
+
+impl LogLine {
+    fn parse(service: &str, output: LogOutput) -> Option<Self> {
+        let (stream, bytes) = match output {
+            LogOutput::StdOut { message } => (LogStream::Stdout, message),
+            LogOutput::StdErr { message } => (LogStream::Stderr, message),
+            LogOutput::Console { message } => (LogStream::Console, message),
+            LogOutput::StdIn { .. } => return None,
+        };
+        let line = String::from_utf8_lossy(&bytes);
+        let line = line.trim_end_matches(['\r', '\n']);
+        // Docker prefixes each line with its RFC 3339 timestamp and a space
+        let (timestamp, message) = match line.split_once(' ') {
+            Some((prefix, message)) => match parse_timestamp(prefix) {
+                Some(timestamp) => (Some(timestamp), message),
+                None => (None, line),
+            },
+            None => (parse_timestamp(line), ""),
+        };
+        Some(Self {
+            service: service.to_string(),
+            stream,
+            timestamp,
+            message: message.to_string(),
+        })
+    }
+}
+
+/// Parses the UTC timestamps Docker writes, such as `2024-05-01T12:00:00.123456789Z`.
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let timestamp = timestamp.strip_suffix('Z')?;
+    let (date, time) = timestamp.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<u64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Days since the epoch of a proleptic Gregorian date, after Howard Hinnant's algorithm
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146097 + day_of_era - 719468).ok()?;
+
+    let nanos = if fraction.is_empty() {
+        0
+    } else {
+        let digits = &fraction[..fraction.len().min(9)];
+        digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32)
+    };
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+impl OrbitStack {
+    /// Streams the logs of a service. The stream ends once the existing lines are read, unless
+    /// `options.follow` is set.
+    pub async fn get_logs(
+        &self,
+        service: &str,
+        options: &LogOptions,
+    ) -> Option<impl Stream<Item = Result<LogLine, bollard::errors::Error>>> {
+        let service = service.to_string();
+        self.containers.get(&service).map(|container_id| {
+            Box::pin(
+                self.docker
+                    .logs(container_id, Some(options.to_docker()))
+                    .filter_map(move |result| match result {
+                        Ok(output) => LogLine::parse(&service, output).map(Ok),
+                        Err(e) => Some(Err(e)),
+                    }),
+            )
+        })
+    }
+
+    /// Collects the logs of a service, never following, so the result is bounded.
+    pub async fn collect_logs(
+        &self,
+        service: &str,
+        options: &LogOptions,
+    ) -> Result<Vec<LogLine>, bollard::errors::Error> {
+        let options = LogOptions {
+            follow: false,
+            ..options.clone()
+        };
+        match self.get_logs(service, &options).await {
+            Some(logs) => logs.collect().await,
+            None => Err(bollard::errors::Error::IOError {
+                err: std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Service {} is not part of the stack", service),
+                ),
+            }),
+        }
+    }
+}
+
+/// The file a service's logs are currently written to.
+struct LogFile {
+    dir: PathBuf,
+    service: String,
+    config: LogShippingConfig,
+    file: File,
+    size: u64,
+    opened_at: SystemTime,
+}
+
+impl LogFile {
+    fn current_path(dir: &Path, service: &str) -> PathBuf {
+        dir.join(format!("{}.log", service))
+    }
+
+    fn open(dir: &Path, service: &str, config: &LogShippingConfig) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::current_path(dir, service))?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            service: service.to_string(),
+            config: config.clone(),
+            file,
+            size,
+            opened_at: SystemTime::now(),
+        })
+    }
+
+    /// Whether the file has to be rotated before `line` is written to it.
+    fn rotation_due(&self, line: &str) -> bool {
+        let age = self.opened_at.elapsed().unwrap_or_default();
+        self.size > 0
+            && (self.size + line.len() as u64 > self.config.max_file_bytes
+                || age.as_secs() >= self.config.max_file_age_secs)
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Moves the current file aside as `<service>.<unix time>.log`, compressing it if set,
+    /// and prunes the oldest rotated files.
+    fn rotate(&mut self) -> io::Result<()> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let rotated = self.dir.join(format!("{}.{}.log", self.service, stamp));
+        fs::rename(Self::current_path(&self.dir, &self.service), &rotated)?;
+        *self = Self::open(&self.dir, &self.service, &self.config)?;
+
+        if self.config.compress {
+            let mut encoder = GzEncoder::new(
+                File::create(rotated.with_extension("log.gz"))?,
+                Compression::default(),
+            );
+            io::copy(&mut File::open(&rotated)?, &mut encoder)?;
+            encoder.finish()?;
+            fs::remove_file(&rotated)?;
+        }
+        self.prune()
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let prefix = format!("{}.", self.service);
+        let mut rotated: Vec<(u128, PathBuf)> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let name = path.file_name()?.to_str()?;
+                let stamp = name
+                    .strip_prefix(&prefix)?
+                    .split('.')
+                    .next()?
+                    .parse()
+                    .ok()?;
+                Some((stamp, path))
+            })
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.config.max_files);
+        for (_, path) in rotated.into_iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows the logs of a container into its service's log files, across restarts of the
+/// container, until it is removed.
+async fn ship_logs(
+    docker: Arc<Docker>,
+    container_id: String,
+    service: String,
+    dir: PathBuf,
+    config: LogShippingConfig,
+) -> io::Result<()> {
+    let mut file = LogFile::open(&dir, &service, &config)?;
+    let mut last_seen: Option<SystemTime> = None;
+    loop {
+        let options = LogOptions {
+            since: last_seen,
+            follow: true,
+            ..Default::default()
+        };
+        let mut logs = docker.logs(&container_id, Some(options.to_docker()));
+        while let Some(output) = logs.next().await {
+            let Ok(output) = output else {
+                break;
+            };
+            let Some(line) = LogLine::parse(&service, output) else {
+                continue;
+            };
+            // `since` has a resolution of a second, so lines may be read again on resuming
+            if let (Some(timestamp), Some(last_seen)) = (line.timestamp, last_seen) {
+                if timestamp <= last_seen {
+                    continue;
+                }
+            }
+            last_seen = line.timestamp.or(last_seen);
+            let stream = match line.stream {
+                LogStream::Stdout => "stdout",
+                LogStream::Stderr => "stderr",
+                LogStream::Console => "console",
+            };
+            let timestamp = line
+                .timestamp
+                .and_then(|timestamp| timestamp.duration_since(UNIX_EPOCH).ok())
+                .map_or_else(String::new, |elapsed| {
+                    format!("{}.{:09}", elapsed.as_secs(), elapsed.subsec_nanos())
+                });
+            let entry = format!("{} {} {}\n", timestamp, stream, line.message);
+            if file.rotation_due(&entry) {
+                // Compressing a full file would hold up a worker of the runtime
+                file = tokio::task::spawn_blocking(move || {
+                    file.rotate()?;
+                    Ok::<_, io::Error>(file)
+                })
+                .await
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+            }
+            file.write(&entry)?;
+        }
+
+        // The stream ends when the container stops; it may be restarted or be gone for good
+        tokio::time::sleep(RESUME_INTERVAL).await;
+        match docker.inspect_container(&container_id, None).await {
+            Ok(_) => continue,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => return Ok(()),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+impl OrbitStack {
+    /// Starts shipping the logs of a service's container, if log shipping is enabled.
+    pub(crate) fn ship_service_logs(&self, service: &str, container_id: &str) {
+        let config = self.config.log_shipping.clone();
+        if !config.enable {
+            return;
+        }
+        let docker = Arc::clone(&self.docker);
+        let container_id = container_id.to_string();
+        let service = service.to_string();
+        let dir = self.config.data_dir.join("logs").join(&service);
+        tokio::spawn(async move {
+            if let Err(e) = ship_logs(docker, container_id, service.clone(), dir, config).await {
+                info!("Stopped shipping the logs of {}: {}", service, e);
+            }
+        });
+    }
+}
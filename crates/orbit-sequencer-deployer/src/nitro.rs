@@ -0,0 +1,602 @@
+//! Nitro node containers: the main node of the stack and any standalone validators.
+
+use crate::events::StackEvent;
+use crate::node_config::{
+    parent_chain_is_l1, sanitize_private_key, BatchPosterSection, BlobClientSection,
+    CachingSection, ChainSection, ConnectionSection, DangerousSection, DataAvailabilitySection,
+    DelayedSequencerSection, ExecutionSection, ExecutionSequencerSection, FeedInputSection,
+    FeedOutputSection, FeedSection, HttpSection, MetricsServerSection, NodeConfig, NodeSection,
+    ParentChainSection, RestAggregatorSection, RpcAggregatorSection, StakerSection, StakerStrategy,
+    WalletSection, WsSection, DEFAULT_FEED_PORT, DEFAULT_HTTP_PORT, DEFAULT_METRICS_PORT,
+    DEFAULT_WS_PORT, METRICS_PATH,
+};
+use crate::probe::{http_request, Prober, PROBE_TIMEOUT};
+use crate::resources::service_kind;
+use crate::{invalid_config, OrbitStack, OrbitStackConfig, NITRO_NODE_IMAGE};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::Config,
+    models::{HostConfig, PortBinding},
+};
+use gadget_sdk::{info, tokio};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Paths inside the nitro-node container
+const NITRO_CONFIG_DIR: &str = "/home/user/config";
+const NITRO_DATA_DIR: &str = "/home/user/.arbitrum";
+
+/// Prefix nitro uses to read config overrides from the environment (`--conf.env-prefix`).
+const ENV_PREFIX: &str = "NITRO";
+const BATCH_POSTER_KEY_ENV: &str = "NITRO_NODE_BATCH__POSTER_PARENT__CHAIN__WALLET_PRIVATE__KEY";
+const STAKER_KEY_ENV: &str = "NITRO_NODE_STAKER_PARENT__CHAIN__WALLET_PRIVATE__KEY";
+
+/// Role of a nitro node in the stack.
+///
+/// `Sequencer`, `BatchPoster`, `FullNode` and `Archive` shape the main `nitro-node` container,
+/// while each `Validator` runs in a container of its own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "role")]
+pub enum NodeRole {
+    /// Orders transactions and publishes the sequencer feed.
+    Sequencer,
+    /// Posts batches to the parent chain, signing with the given key. Batches go out as
+    /// EIP-4844 blobs when `post_4844_blobs` is set, and as calldata otherwise.
+    BatchPoster {
+        private_key: String,
+        #[serde(default)]
+        post_4844_blobs: bool,
+    },
+    /// Validates assertions with the given strategy, staking with the given key.
+    Validator {
+        private_key: String,
+        #[serde(default)]
+        strategy: StakerStrategy,
+    },
+    /// Follows the chain and forwards transactions to `sequencer_endpoint`.
+    FullNode,
+    /// Keeps all historical state instead of pruning it.
+    Archive,
+}
+
+/// Endpoints of the main `nitro-node` container and how they are published on the host.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeEndpoints {
+    /// RPC namespaces served over HTTP and WebSocket.
+    pub api: Vec<String>,
+    /// HTTP RPC, which is always served.
+    pub http: Endpoint,
+    /// WebSocket RPC.
+    pub ws: Endpoint,
+    /// Sequencer feed, only served by sequencers.
+    pub feed: Endpoint,
+    /// Prometheus metrics, served by validators too when enabled.
+    pub metrics: Endpoint,
+}
+
+impl Default for NodeEndpoints {
+    fn default() -> Self {
+        Self {
+            api: ["net", "web3", "eth"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            http: Endpoint::published(DEFAULT_HTTP_PORT),
+            ws: Endpoint {
+                enable: false,
+                publish: None,
+            },
+            feed: Endpoint {
+                enable: true,
+                publish: None,
+            },
+            metrics: Endpoint {
+                enable: true,
+                publish: None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Endpoint {
+    pub enable: bool,
+    /// Host binding of the endpoint; other containers of the stack reach it either way.
+    #[serde(default)]
+    pub publish: Option<HostPort>,
+}
+
+impl Endpoint {
+    /// An endpoint published on the loopback interface, on the same port as in the container.
+    fn published(port: u16) -> Self {
+        Self {
+            enable: true,
+            publish: Some(HostPort {
+                host_ip: "127.0.0.1".to_string(),
+                host_port: port,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostPort {
+    pub host_ip: String,
+    pub host_port: u16,
+}
+
+impl OrbitStackConfig {
+    /// Ports of the main node that are served, each with its host binding if it is published.
+    pub(crate) fn node_ports(&self) -> Vec<(u16, Option<HostPort>)> {
+        let endpoints = &self.endpoints;
+        let mut ports = vec![(DEFAULT_HTTP_PORT, endpoints.http.publish.clone())];
+        if endpoints.ws.enable {
+            ports.push((DEFAULT_WS_PORT, endpoints.ws.publish.clone()));
+        }
+        if self.feed_enabled() {
+            ports.push((DEFAULT_FEED_PORT, endpoints.feed.publish.clone()));
+        }
+        if endpoints.metrics.enable {
+            ports.push((DEFAULT_METRICS_PORT, endpoints.metrics.publish.clone()));
+        }
+        ports
+    }
+
+    /// Whether the main node publishes the sequencer feed.
+    pub fn feed_enabled(&self) -> bool {
+        self.is_sequencer() && self.endpoints.feed.enable
+    }
+
+    pub fn is_sequencer(&self) -> bool {
+        self.roles.contains(&NodeRole::Sequencer)
+    }
+
+    pub fn is_archive(&self) -> bool {
+        self.roles.contains(&NodeRole::Archive)
+    }
+
+    fn batch_poster(&self) -> Option<(&str, bool)> {
+        self.roles.iter().find_map(|role| match role {
+            NodeRole::BatchPoster {
+                private_key,
+                post_4844_blobs,
+            } => Some((private_key.as_str(), *post_4844_blobs)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn validators(&self) -> impl Iterator<Item = (&str, StakerStrategy)> {
+        self.roles.iter().filter_map(|role| match role {
+            NodeRole::Validator {
+                private_key,
+                strategy,
+            } => Some((private_key.as_str(), *strategy)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn validate_roles(&self) -> Result<(), bollard::errors::Error> {
+        if self.is_sequencer() && self.roles.contains(&NodeRole::FullNode) {
+            return Err(invalid_config(
+                "a node cannot be both a sequencer and a full node",
+            ));
+        }
+        if self
+            .roles
+            .iter()
+            .filter(|role| matches!(role, NodeRole::BatchPoster { .. }))
+            .count()
+            > 1
+        {
+            return Err(invalid_config("at most one batch poster role is supported"));
+        }
+        if !self.is_sequencer() && self.sequencer_endpoint.is_none() {
+            return Err(invalid_config(
+                "sequencer_endpoint is required when the stack does not run the sequencer",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_endpoints(&self) -> Result<(), bollard::errors::Error> {
+        if !self.endpoints.http.enable {
+            return Err(invalid_config("the HTTP RPC endpoint cannot be disabled"));
+        }
+        if self.endpoints.api.is_empty() {
+            return Err(invalid_config("at least one RPC namespace must be served"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_parent_chain(&self) -> Result<(), bollard::errors::Error> {
+        let parent_is_l1 = parent_chain_is_l1(self.parent_chain_id);
+        // Since Dencun, batches on an L1 may live in blobs that only a beacon node serves
+        if parent_is_l1 && self.parent_chain_beacon_rpc.is_none() {
+            return Err(invalid_config(
+                "parent_chain_beacon_rpc is required when the parent chain is an L1",
+            ));
+        }
+        if !parent_is_l1 && matches!(self.batch_poster(), Some((_, true))) {
+            return Err(invalid_config(
+                "batches can only be posted as blobs to an L1 parent chain",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Service name of the `index`-th validator container.
+pub(crate) fn validator_service(index: usize) -> String {
+    format!("nitro-validator-{}", index)
+}
+
+impl OrbitStack {
+    /// Configuration shared by every nitro container of the stack: a non-sequencing node
+    /// forwarding transactions to `forwarding_target`.
+    fn base_node_config(&self, forwarding_target: String) -> NodeConfig {
+        // Add DAS configuration if enabled, reading from our own committee, mirror and any others
+        let data_availability = self.config.das_enabled().then(|| {
+            let mut rest_urls = self.das_rest_urls();
+            rest_urls.extend(self.das_mirror_url());
+            rest_urls.extend(self.config.das_endpoints.iter().flatten().cloned());
+            DataAvailabilitySection {
+                enable: true,
+                sequencer_inbox_address: self
+                    .config
+                    .chain_info()
+                    .ok()
+                    .map(|info| info.rollup.sequencer_inbox),
+                parent_chain_node_url: Some(self.config.parent_chain_rpc.clone()),
+                rest_aggregator: (!rest_urls.is_empty()
+                    || self.config.das_online_url_list.is_some())
+                .then(|| RestAggregatorSection {
+                    enable: true,
+                    urls: rest_urls,
+                    online_url_list: self.config.das_online_url_list.clone(),
+                }),
+                rpc_aggregator: None,
+            }
+        });
+
+        NodeConfig {
+            chain: ChainSection {
+                info_json: self.config.chain_info_json.clone(),
+                name: self.config.chain_name.clone(),
+            },
+            parent_chain: ParentChainSection {
+                connection: ConnectionSection {
+                    url: self.config.parent_chain_rpc.clone(),
+                },
+                blob_client: self
+                    .config
+                    .parent_chain_beacon_rpc
+                    .clone()
+                    .map(|beacon_url| BlobClientSection { beacon_url }),
+            },
+            http: HttpSection {
+                addr: "0.0.0.0".to_string(),
+                port: DEFAULT_HTTP_PORT,
+                vhosts: "*".to_string(),
+                corsdomain: "*".to_string(),
+                api: self.config.endpoints.api.clone(),
+            },
+            ws: self.config.endpoints.ws.enable.then(|| WsSection {
+                addr: "0.0.0.0".to_string(),
+                port: DEFAULT_WS_PORT,
+                origins: "*".to_string(),
+                api: self.config.endpoints.api.clone(),
+            }),
+            metrics: self.config.endpoints.metrics.enable,
+            metrics_server: self
+                .config
+                .endpoints
+                .metrics
+                .enable
+                .then(|| MetricsServerSection {
+                    addr: "0.0.0.0".to_string(),
+                    port: DEFAULT_METRICS_PORT,
+                    update_interval: "3s".to_string(),
+                }),
+            node: NodeSection {
+                sequencer: false,
+                delayed_sequencer: None,
+                batch_poster: None,
+                staker: None,
+                dangerous: None,
+                feed: None,
+                data_availability,
+            },
+            execution: ExecutionSection {
+                forwarding_target,
+                sequencer: None,
+                caching: None,
+            },
+        }
+    }
+
+    /// Renders the configuration of the main `nitro-node` container.
+    pub fn node_config(&self) -> NodeConfig {
+        let is_sequencer = self.config.is_sequencer();
+        let forwarding_target = if is_sequencer {
+            String::new()
+        } else {
+            self.config.sequencer_endpoint.clone().unwrap_or_default()
+        };
+        let mut node_config = self.base_node_config(forwarding_target);
+
+        if is_sequencer {
+            node_config.node.sequencer = true;
+            node_config.node.delayed_sequencer = Some(DelayedSequencerSection {
+                enable: true,
+                use_merge_finality: false,
+                finalize_distance: 1,
+            });
+            node_config.node.dangerous = Some(DangerousSection {
+                no_sequencer_coordinator: true,
+            });
+            node_config.node.feed = self.config.feed_enabled().then(|| FeedSection {
+                input: None,
+                output: Some(FeedOutputSection {
+                    enable: true,
+                    addr: "0.0.0.0".to_string(),
+                    port: DEFAULT_FEED_PORT,
+                }),
+            });
+            node_config.execution.sequencer = Some(ExecutionSequencerSection {
+                enable: true,
+                max_tx_data_size: 85000,
+                max_block_speed: "250ms".to_string(),
+            });
+        }
+
+        // The key itself is injected through the environment
+        if let Some((_, post_4844_blobs)) = self.config.batch_poster() {
+            node_config.node.batch_poster = Some(BatchPosterSection {
+                enable: true,
+                max_size: 90000,
+                post_4844_blobs: Some(post_4844_blobs),
+                parent_chain_wallet: WalletSection::default(),
+            });
+
+            // The batch poster has the committee sign each batch before posting its certificate
+            if let (Some(keyset), Some(data_availability)) = (
+                self.das_keyset(),
+                node_config.node.data_availability.as_mut(),
+            ) {
+                if let Ok(backends) = serde_json::to_string(&keyset.backends) {
+                    data_availability.rpc_aggregator = Some(RpcAggregatorSection {
+                        enable: true,
+                        assumed_honest: keyset.assumed_honest,
+                        backends,
+                    });
+                }
+            }
+        }
+
+        if self.config.is_archive() {
+            node_config.execution.caching = Some(CachingSection { archive: true });
+        }
+
+        node_config
+    }
+
+    /// Renders the configuration of a standalone validator container. Validators reach the
+    /// stack's own node on the stack's network, and follow its feed when it publishes one.
+    pub fn validator_node_config(&self, strategy: StakerStrategy) -> NodeConfig {
+        let (forwarding_target, feed) = match &self.config.sequencer_endpoint {
+            Some(endpoint) if !self.config.is_sequencer() => (endpoint.clone(), None),
+            _ => (
+                format!("http://nitro-node:{}", DEFAULT_HTTP_PORT),
+                self.config.feed_enabled().then(|| FeedSection {
+                    input: Some(FeedInputSection {
+                        url: vec![format!("ws://nitro-node:{}", DEFAULT_FEED_PORT)],
+                    }),
+                    output: None,
+                }),
+            ),
+        };
+        let mut node_config = self.base_node_config(forwarding_target);
+        node_config.node.feed = feed;
+        node_config.node.staker = Some(StakerSection {
+            enable: true,
+            strategy,
+            parent_chain_wallet: WalletSection::default(),
+        });
+        node_config
+    }
+
+    /// Writes the node config of `service` to `{data_dir}/{service}/config` and makes sure its
+    /// chain data directory exists.
+    fn write_node_config(
+        &self,
+        service: &str,
+        node_config: &NodeConfig,
+    ) -> Result<(), bollard::errors::Error> {
+        let service_dir = self.config.data_dir.join(service);
+        std::fs::create_dir_all(service_dir.join("config"))?;
+        std::fs::create_dir_all(service_dir.join("data"))?;
+
+        let contents = serde_json::to_string_pretty(node_config).map_err(|e| {
+            bollard::errors::Error::IOError {
+                err: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            }
+        })?;
+        std::fs::write(
+            service_dir.join("config").join("node-config.json"),
+            contents,
+        )?;
+        Ok(())
+    }
+
+    /// Hands the data directory of `service` to the user nitro runs as.
+    async fn run_nitro_init(&self, service: &str) -> Result<(), bollard::errors::Error> {
+        let config = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            user: Some("root".to_string()),
+            entrypoint: Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("chown -R 1000:1000 {}", NITRO_DATA_DIR),
+            ]),
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!(
+                    "{}/{}/data:{}",
+                    self.config.data_dir.display(),
+                    service,
+                    NITRO_DATA_DIR
+                )]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.run_to_completion(&format!("{}-init", service), config)
+            .await?;
+        Ok(())
+    }
+
+    /// Starts a nitro container for `service` running `node_config`, with `env` carrying any
+    /// wallet keys. Each of `ports` is exposed, and published on the host if it has a binding.
+    async fn start_nitro_container(
+        &mut self,
+        service: &str,
+        node_config: &NodeConfig,
+        env: Vec<String>,
+        ports: Vec<(u16, Option<HostPort>)>,
+    ) -> Result<(), bollard::errors::Error> {
+        self.write_node_config(service, node_config)?;
+        self.run_nitro_init(service).await?;
+
+        // Flags given on the command line take precedence over the config file
+        let mut cmd_args = vec![
+            format!("--conf.file={}/node-config.json", NITRO_CONFIG_DIR),
+            format!("--conf.env-prefix={}", ENV_PREFIX),
+        ];
+        cmd_args.extend(self.config.extra_node_args.iter().cloned());
+
+        let port_bindings: HashMap<_, _> = ports
+            .iter()
+            .filter_map(|(port, publish)| {
+                publish.as_ref().map(|publish| {
+                    (
+                        format!("{}/tcp", port),
+                        Some(vec![PortBinding {
+                            host_ip: Some(publish.host_ip.clone()),
+                            host_port: Some(publish.host_port.to_string()),
+                        }]),
+                    )
+                })
+            })
+            .collect();
+
+        let config = Config {
+            image: Some(NITRO_NODE_IMAGE.to_string()),
+            exposed_ports: Some(
+                ports
+                    .iter()
+                    .map(|(port, _)| (format!("{}/tcp", port), HashMap::new()))
+                    .collect(),
+            ),
+            env: Some(env),
+            host_config: Some(HostConfig {
+                extra_hosts: Some(vec!["host.docker.internal:host-gateway".to_string()]),
+                port_bindings: Some(port_bindings),
+                binds: Some(vec![
+                    format!(
+                        "{}/{}/config:{}:ro",
+                        self.config.data_dir.display(),
+                        service,
+                        NITRO_CONFIG_DIR
+                    ),
+                    format!(
+                        "{}/{}/data:{}",
+                        self.config.data_dir.display(),
+                        service,
+                        NITRO_DATA_DIR
+                    ),
+                ]),
+                ..Default::default()
+            }),
+            cmd: Some(cmd_args),
+            ..Default::default()
+        };
+
+        self.create_and_start_container(service, config).await
+    }
+
+    pub(crate) async fn start_nitro_node(&mut self) -> Result<(), bollard::errors::Error> {
+        let node_config = self.node_config();
+        let env = self
+            .config
+            .batch_poster()
+            .map(|(key, _)| format!("{}={}", BATCH_POSTER_KEY_ENV, sanitize_private_key(key)))
+            .into_iter()
+            .collect();
+
+        let ports = self.config.node_ports();
+        self.start_nitro_container("nitro-node", &node_config, env, ports)
+            .await
+    }
+
+    /// Recreates the main node so it picks up a changed configuration.
+    pub(crate) async fn restart_nitro_node(&mut self) -> Result<(), bollard::errors::Error> {
+        self.remove_container("nitro-node").await?;
+        self.emit(StackEvent::ConfigChanged {
+            service: "nitro-node".to_string(),
+        });
+        self.start_nitro_node().await
+    }
+
+    /// Scrapes the Prometheus metrics of the stack's nitro nodes. See [`Prober::nitro_metrics`].
+    pub async fn nitro_metrics(&self) -> Result<Vec<(String, String)>, bollard::errors::Error> {
+        self.prober().nitro_metrics().await
+    }
+
+    pub(crate) async fn start_validators(&mut self) -> Result<(), bollard::errors::Error> {
+        let validators: Vec<(String, StakerStrategy)> = self
+            .config
+            .validators()
+            .map(|(key, strategy)| (sanitize_private_key(key), strategy))
+            .collect();
+
+        for (index, (key, strategy)) in validators.into_iter().enumerate() {
+            let node_config = self.validator_node_config(strategy);
+            let env = vec![format!("{}={}", STAKER_KEY_ENV, key)];
+            let mut ports = vec![(DEFAULT_HTTP_PORT, None)];
+            if self.config.endpoints.metrics.enable {
+                ports.push((DEFAULT_METRICS_PORT, None));
+            }
+            self.start_nitro_container(&validator_service(index), &node_config, env, ports)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Prober {
+    /// Scrapes the Prometheus metrics of the stack's nitro nodes, by service. Nodes that do not
+    /// answer are left out.
+    pub async fn nitro_metrics(&self) -> Result<Vec<(String, String)>, bollard::errors::Error> {
+        let services = self
+            .services()
+            .into_iter()
+            .filter(|service| matches!(service_kind(service), "nitro-node" | "nitro-validator"));
+
+        let mut metrics = Vec::new();
+        for service in services {
+            let Some(ip) = self.service_ip(service).await? else {
+                continue;
+            };
+            let addr = format!("{}:{}", ip, DEFAULT_METRICS_PORT);
+            let scrape = http_request(&addr, "GET", METRICS_PATH, None);
+            match tokio::time::timeout(PROBE_TIMEOUT, scrape).await {
+                Ok(Ok((200, body))) => metrics.push((service.clone(), body)),
+                Ok(Ok((status, _))) => info!("Metrics of {} returned {}", service, status),
+                Ok(Err(e)) => info!("Failed to scrape the metrics of {}: {}", service, e),
+                Err(_) => info!("Metrics of {} timed out", service),
+            }
+        }
+        Ok(metrics)
+    }
+}
@@ -0,0 +1,629 @@
+//! Typed nitro `node-config.json` and chain info structures.
+//!
+//! These mirror `prepareChainConfig` and `prepareNodeConfig` from the Arbitrum orbit-sdk so the
+//! node configuration can be derived in Rust straight from a rollup deployment.
+
+use alloy_primitives::{Address, B256};
+use serde::{Deserialize, Serialize};
+
+/// Default HTTP RPC port of the nitro node.
+pub const DEFAULT_HTTP_PORT: u16 = 8449;
+/// Port the sequencer feed is published on by the nitro node.
+pub const DEFAULT_FEED_PORT: u16 = 9642;
+/// Default WebSocket RPC port of the nitro node.
+pub const DEFAULT_WS_PORT: u16 = 8548;
+/// Default port of the nitro node's metrics server.
+pub const DEFAULT_METRICS_PORT: u16 = 6070;
+/// Path the nitro node serves Prometheus metrics at.
+pub const METRICS_PATH: &str = "/debug/metrics/prometheus";
+/// URL of the local DAS server's REST and RPC endpoints in the orbit-sdk's default committee.
+const DEFAULT_DAS_URL: &str = "http://localhost:9876";
+/// Placeholder BLS key used by the orbit-sdk for the default single-member committee.
+const DEFAULT_DAS_PUBKEY: &str = "YAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA==";
+
+/// Ethereum L1 chains an orbit chain can settle to directly.
+const L1_CHAIN_IDS: [u64; 4] = [1, 1337, 17000, 11155111];
+/// Arbitrum chains an orbit chain can settle to as an L3.
+const ARBITRUM_CHAIN_IDS: [u64; 4] = [42161, 42170, 421614, 412346];
+
+/// Returns `true` if the parent chain is an Ethereum L1.
+pub fn parent_chain_is_l1(parent_chain_id: u64) -> bool {
+    L1_CHAIN_IDS.contains(&parent_chain_id)
+}
+
+/// Returns `true` if the parent chain is itself an Arbitrum chain.
+pub fn parent_chain_is_arbitrum(parent_chain_id: u64) -> bool {
+    ARBITRUM_CHAIN_IDS.contains(&parent_chain_id)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    pub homestead_block: u64,
+    pub dao_fork_block: Option<u64>,
+    pub dao_fork_support: bool,
+    pub eip150_block: u64,
+    pub eip150_hash: B256,
+    pub eip155_block: u64,
+    pub eip158_block: u64,
+    pub byzantium_block: u64,
+    pub constantinople_block: u64,
+    pub petersburg_block: u64,
+    pub istanbul_block: u64,
+    pub muir_glacier_block: u64,
+    pub berlin_block: u64,
+    pub london_block: u64,
+    pub clique: CliqueConfig,
+    pub arbitrum: ArbitrumChainParams,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CliqueConfig {
+    pub period: u64,
+    pub epoch: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ArbitrumChainParams {
+    #[serde(rename = "EnableArbOS")]
+    pub enable_arb_os: bool,
+    pub allow_debug_precompiles: bool,
+    pub data_availability_committee: bool,
+    #[serde(rename = "InitialArbOSVersion")]
+    pub initial_arb_os_version: u64,
+    pub initial_chain_owner: Address,
+    pub genesis_block_num: u64,
+    pub max_code_size: u64,
+    pub max_init_code_size: u64,
+}
+
+impl ChainConfig {
+    /// Chain config with the orbit-sdk defaults for the given chain.
+    pub fn new(chain_id: u64, owner: Address, data_availability_committee: bool) -> Self {
+        Self {
+            chain_id,
+            homestead_block: 0,
+            dao_fork_block: None,
+            dao_fork_support: true,
+            eip150_block: 0,
+            eip150_hash: B256::ZERO,
+            eip155_block: 0,
+            eip158_block: 0,
+            byzantium_block: 0,
+            constantinople_block: 0,
+            petersburg_block: 0,
+            istanbul_block: 0,
+            muir_glacier_block: 0,
+            berlin_block: 0,
+            london_block: 0,
+            clique: CliqueConfig {
+                period: 0,
+                epoch: 0,
+            },
+            arbitrum: ArbitrumChainParams {
+                enable_arb_os: true,
+                allow_debug_precompiles: false,
+                data_availability_committee,
+                initial_arb_os_version: 32,
+                initial_chain_owner: owner,
+                genesis_block_num: 0,
+                max_code_size: 24576,
+                max_init_code_size: 49152,
+            },
+        }
+    }
+}
+
+/// Core rollup contracts, as emitted by the `RollupCreated` event of the rollup creator.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreContracts {
+    pub rollup: Address,
+    pub native_token: Address,
+    pub inbox: Address,
+    pub outbox: Address,
+    pub rollup_event_inbox: Address,
+    pub challenge_manager: Address,
+    pub admin_proxy: Address,
+    pub sequencer_inbox: Address,
+    pub bridge: Address,
+    pub upgrade_executor: Address,
+    pub validator_utils: Address,
+    pub validator_wallet_creator: Address,
+    /// Parent chain block the rollup was created in.
+    pub deployed_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChainInfo {
+    pub chain_id: u64,
+    pub parent_chain_id: u64,
+    pub parent_chain_is_arbitrum: bool,
+    pub chain_name: String,
+    pub chain_config: ChainConfig,
+    pub rollup: RollupInfo,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RollupInfo {
+    pub bridge: Address,
+    pub inbox: Address,
+    pub sequencer_inbox: Address,
+    pub rollup: Address,
+    pub validator_utils: Address,
+    pub validator_wallet_creator: Address,
+    pub deployed_at: u64,
+}
+
+impl ChainInfo {
+    pub fn new(
+        chain_name: String,
+        chain_config: ChainConfig,
+        core_contracts: &CoreContracts,
+        parent_chain_id: u64,
+    ) -> Self {
+        Self {
+            chain_id: chain_config.chain_id,
+            parent_chain_id,
+            parent_chain_is_arbitrum: parent_chain_is_arbitrum(parent_chain_id),
+            chain_name,
+            chain_config,
+            rollup: RollupInfo {
+                bridge: core_contracts.bridge,
+                inbox: core_contracts.inbox,
+                sequencer_inbox: core_contracts.sequencer_inbox,
+                rollup: core_contracts.rollup,
+                validator_utils: core_contracts.validator_utils,
+                validator_wallet_creator: core_contracts.validator_wallet_creator,
+                deployed_at: core_contracts.deployed_at,
+            },
+        }
+    }
+
+    /// Renders the value of nitro's `--chain.info-json`, which is a JSON array of chain infos.
+    pub fn to_info_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&[self])
+    }
+}
+
+/// Inputs to [`prepare_node_config`], equivalent to the orbit-sdk's `PrepareNodeConfigParams`.
+#[derive(Clone, Debug)]
+pub struct NodeConfigParams {
+    pub chain_name: String,
+    pub chain_config: ChainConfig,
+    pub core_contracts: CoreContracts,
+    pub batch_poster_private_key: String,
+    pub validator_private_key: String,
+    pub parent_chain_id: u64,
+    pub parent_chain_rpc_url: String,
+    pub parent_chain_beacon_rpc_url: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NodeConfig {
+    pub chain: ChainSection,
+    pub parent_chain: ParentChainSection,
+    pub http: HttpSection,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ws: Option<WsSection>,
+    #[serde(default)]
+    pub metrics: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_server: Option<MetricsServerSection>,
+    pub node: NodeSection,
+    pub execution: ExecutionSection,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChainSection {
+    pub info_json: String,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParentChainSection {
+    pub connection: ConnectionSection,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blob_client: Option<BlobClientSection>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionSection {
+    pub url: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BlobClientSection {
+    pub beacon_url: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HttpSection {
+    pub addr: String,
+    pub port: u16,
+    pub vhosts: String,
+    pub corsdomain: String,
+    pub api: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WsSection {
+    pub addr: String,
+    pub port: u16,
+    pub origins: String,
+    pub api: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MetricsServerSection {
+    pub addr: String,
+    pub port: u16,
+    pub update_interval: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NodeSection {
+    pub sequencer: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delayed_sequencer: Option<DelayedSequencerSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_poster: Option<BatchPosterSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub staker: Option<StakerSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dangerous: Option<DangerousSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed: Option<FeedSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_availability: Option<DataAvailabilitySection>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DelayedSequencerSection {
+    pub enable: bool,
+    pub use_merge_finality: bool,
+    pub finalize_distance: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BatchPosterSection {
+    pub enable: bool,
+    pub max_size: u64,
+    /// Post batches as EIP-4844 blobs instead of calldata. Needs an L1 parent chain.
+    #[serde(rename = "post-4844-blobs", skip_serializing_if = "Option::is_none")]
+    pub post_4844_blobs: Option<bool>,
+    pub parent_chain_wallet: WalletSection,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StakerSection {
+    pub enable: bool,
+    pub strategy: StakerStrategy,
+    pub parent_chain_wallet: WalletSection,
+}
+
+/// How a validator participates in assertions, from least to most active.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StakerStrategy {
+    /// Only watches for and reports invalid assertions.
+    #[default]
+    Watchtower,
+    /// Stakes only when an invalid assertion is found.
+    Defensive,
+    /// Stays staked on the latest valid assertion.
+    StakeLatest,
+    /// Stakes and resolves assertions.
+    ResolveNodes,
+    /// Creates new assertions in addition to resolving them.
+    MakeNodes,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WalletSection {
+    /// Left out when the key is injected through the environment instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DangerousSection {
+    pub no_sequencer_coordinator: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input: Option<FeedInputSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<FeedOutputSection>,
+}
+
+/// Sequencer feeds a node follows to learn of new blocks before they are posted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedInputSection {
+    pub url: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FeedOutputSection {
+    pub enable: bool,
+    pub addr: String,
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DataAvailabilitySection {
+    pub enable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequencer_inbox_address: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_chain_node_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rest_aggregator: Option<RestAggregatorSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_aggregator: Option<RpcAggregatorSection>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RestAggregatorSection {
+    pub enable: bool,
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub online_url_list: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RpcAggregatorSection {
+    pub enable: bool,
+    pub assumed_honest: u64,
+    /// JSON-encoded list of [`DasBackend`]s.
+    pub backends: String,
+}
+
+/// A DAS committee member as listed in the RPC aggregator's `backends`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DasBackend {
+    pub url: String,
+    pub pubkey: String,
+    /// Assigned from the member's position when a keyset is encoded.
+    #[serde(default)]
+    pub signermask: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecutionSection {
+    pub forwarding_target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequencer: Option<ExecutionSequencerSection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caching: Option<CachingSection>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExecutionSequencerSection {
+    pub enable: bool,
+    pub max_tx_data_size: u64,
+    pub max_block_speed: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CachingSection {
+    pub archive: bool,
+}
+
+/// Nitro expects wallet keys as bare hex.
+pub fn sanitize_private_key(key: &str) -> String {
+    key.trim_start_matches("0x").to_string()
+}
+
+/// Builds the full node configuration of a sequencer that also posts batches and stakes,
+/// matching the orbit-sdk's `prepareNodeConfig`.
+pub fn prepare_node_config(params: NodeConfigParams) -> Result<NodeConfig, serde_json::Error> {
+    let data_availability = params
+        .chain_config
+        .arbitrum
+        .data_availability_committee
+        .then(|| {
+            serde_json::to_string(&[DasBackend {
+                url: DEFAULT_DAS_URL.to_string(),
+                pubkey: DEFAULT_DAS_PUBKEY.to_string(),
+                signermask: 1,
+            }])
+            .map(|backends| DataAvailabilitySection {
+                enable: true,
+                sequencer_inbox_address: Some(params.core_contracts.sequencer_inbox),
+                parent_chain_node_url: Some(params.parent_chain_rpc_url.clone()),
+                rest_aggregator: Some(RestAggregatorSection {
+                    enable: true,
+                    urls: vec![DEFAULT_DAS_URL.to_string()],
+                    online_url_list: None,
+                }),
+                rpc_aggregator: Some(RpcAggregatorSection {
+                    enable: true,
+                    assumed_honest: 1,
+                    backends,
+                }),
+            })
+        })
+        .transpose()?;
+
+    let chain_info = ChainInfo::new(
+        params.chain_name.clone(),
+        params.chain_config,
+        &params.core_contracts,
+        params.parent_chain_id,
+    );
+
+    Ok(NodeConfig {
+        chain: ChainSection {
+            info_json: chain_info.to_info_json()?,
+            name: params.chain_name,
+        },
+        parent_chain: ParentChainSection {
+            connection: ConnectionSection {
+                url: params.parent_chain_rpc_url,
+            },
+            blob_client: params
+                .parent_chain_beacon_rpc_url
+                .map(|beacon_url| BlobClientSection { beacon_url }),
+        },
+        http: HttpSection {
+            addr: "0.0.0.0".to_string(),
+            port: DEFAULT_HTTP_PORT,
+            vhosts: "*".to_string(),
+            corsdomain: "*".to_string(),
+            api: ["eth", "net", "web3", "arb", "debug"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        },
+        ws: None,
+        metrics: false,
+        metrics_server: None,
+        node: NodeSection {
+            sequencer: true,
+            delayed_sequencer: Some(DelayedSequencerSection {
+                enable: true,
+                use_merge_finality: false,
+                finalize_distance: 1,
+            }),
+            batch_poster: Some(BatchPosterSection {
+                enable: true,
+                max_size: 90000,
+                post_4844_blobs: None,
+                parent_chain_wallet: WalletSection {
+                    private_key: Some(sanitize_private_key(&params.batch_poster_private_key)),
+                },
+            }),
+            staker: Some(StakerSection {
+                enable: true,
+                strategy: StakerStrategy::MakeNodes,
+                parent_chain_wallet: WalletSection {
+                    private_key: Some(sanitize_private_key(&params.validator_private_key)),
+                },
+            }),
+            dangerous: Some(DangerousSection {
+                no_sequencer_coordinator: true,
+            }),
+            feed: None,
+            data_availability,
+        },
+        execution: ExecutionSection {
+            forwarding_target: String::new(),
+            sequencer: Some(ExecutionSequencerSection {
+                enable: true,
+                max_tx_data_size: 85000,
+                max_block_speed: "250ms".to_string(),
+            }),
+            caching: Some(CachingSection { archive: true }),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    const CHAIN_INFO: &str = include_str!("../testdata/chain-info.json");
+    const NODE_CONFIG: &str = include_str!("../testdata/node-config.json");
+
+    fn core_contracts() -> CoreContracts {
+        CoreContracts {
+            rollup: Address::repeat_byte(0x01),
+            native_token: Address::ZERO,
+            inbox: Address::repeat_byte(0x03),
+            outbox: Address::repeat_byte(0x04),
+            rollup_event_inbox: Address::repeat_byte(0x05),
+            challenge_manager: Address::repeat_byte(0x06),
+            admin_proxy: Address::repeat_byte(0x07),
+            sequencer_inbox: Address::repeat_byte(0x08),
+            bridge: Address::repeat_byte(0x09),
+            upgrade_executor: Address::repeat_byte(0x10),
+            validator_utils: Address::repeat_byte(0x12),
+            validator_wallet_creator: Address::repeat_byte(0x13),
+            deployed_at: 6_500_000,
+        }
+    }
+
+    fn chain_config() -> ChainConfig {
+        ChainConfig::new(97400766948, Address::repeat_byte(0x11), true)
+    }
+
+    fn params() -> NodeConfigParams {
+        NodeConfigParams {
+            chain_name: "orbit-test".to_string(),
+            chain_config: chain_config(),
+            core_contracts: core_contracts(),
+            batch_poster_private_key: format!("0x{}", "aa".repeat(32)),
+            validator_private_key: "bb".repeat(32),
+            parent_chain_id: 11155111,
+            parent_chain_rpc_url: "https://sepolia.example.com".to_string(),
+            parent_chain_beacon_rpc_url: Some("https://beacon.example.com".to_string()),
+        }
+    }
+
+    fn golden(contents: &str) -> Value {
+        serde_json::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn chain_info_matches_golden() {
+        let info = ChainInfo::new(
+            "orbit-test".to_string(),
+            chain_config(),
+            &core_contracts(),
+            11155111,
+        );
+        let rendered: Value = serde_json::from_str(&info.to_info_json().unwrap()).unwrap();
+        assert_eq!(rendered, golden(CHAIN_INFO));
+    }
+
+    #[test]
+    fn node_config_matches_golden() {
+        let config = prepare_node_config(params()).unwrap();
+        assert_eq!(serde_json::to_value(&config).unwrap(), golden(NODE_CONFIG));
+    }
+
+    #[test]
+    fn node_config_round_trips() {
+        let config = prepare_node_config(params()).unwrap();
+        let parsed: NodeConfig = serde_json::from_str(NODE_CONFIG).unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn chain_without_committee_has_no_data_availability() {
+        let mut params = params();
+        params.chain_config = ChainConfig::new(97400766948, Address::repeat_byte(0x11), false);
+        params.parent_chain_beacon_rpc_url = None;
+        let config = prepare_node_config(params).unwrap();
+        assert_eq!(config.node.data_availability, None);
+        assert_eq!(config.parent_chain.blob_client, None);
+
+        let rendered = serde_json::to_value(&config).unwrap();
+        assert!(rendered["node"].get("data-availability").is_none());
+        assert!(rendered["parent-chain"].get("blob-client").is_none());
+    }
+}
@@ -0,0 +1,119 @@
+//! Platform the stack's containers run on, derived from the host architecture and overridable
+//! per service, with a pre-flight check that every image is published for its platform.
+
+use crate::das::{das_service, DAS_MIRROR_SERVICE};
+use crate::nitro::validator_service;
+use crate::proxy::NGINX_IMAGE;
+use crate::resources::service_kind;
+use crate::{invalid_config, OrbitStack, OrbitStackConfig, NITRO_NODE_IMAGE};
+use gadget_sdk::docker::bollard;
+use std::collections::BTreeSet;
+
+/// Docker platform of the host, such as `linux/amd64` or `linux/arm64`.
+pub fn host_platform() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        "powerpc64" => "ppc64le",
+        other => other,
+    };
+    format!("linux/{}", arch)
+}
+
+/// Whether an image platform satisfies a `os/arch[/variant]` platform string.
+fn platform_matches(
+    platform: &str,
+    os: Option<&str>,
+    architecture: Option<&str>,
+    variant: Option<&str>,
+) -> bool {
+    let mut parts = platform.split('/');
+    let wanted_os = parts.next();
+    let wanted_arch = parts.next();
+    let wanted_variant = parts.next();
+    wanted_os == os
+        && wanted_arch == architecture
+        && (wanted_variant.is_none() || wanted_variant == variant)
+}
+
+impl OrbitStackConfig {
+    /// Platform of a service: its own entry in `platforms`, then the entry of its kind, then
+    /// the host's platform.
+    pub fn platform_for(&self, service: &str) -> String {
+        self.platforms
+            .get(service)
+            .or_else(|| self.platforms.get(service_kind(service)))
+            .cloned()
+            .unwrap_or_else(host_platform)
+    }
+
+    /// Images of every long-running service this configuration starts, by service.
+    fn service_images(&self) -> Vec<(String, String)> {
+        let mut images = vec![("nitro-node".to_string(), NITRO_NODE_IMAGE.to_string())];
+        for index in 0..self.validators().count() {
+            images.push((validator_service(index), NITRO_NODE_IMAGE.to_string()));
+        }
+        let members = self
+            .das_committee
+            .as_ref()
+            .map_or(0, |committee| committee.members);
+        for index in 0..members {
+            images.push((das_service(index), NITRO_NODE_IMAGE.to_string()));
+        }
+        if self.das_mirror.is_some() {
+            images.push((DAS_MIRROR_SERVICE.to_string(), NITRO_NODE_IMAGE.to_string()));
+        }
+        images.extend(
+            self.explorer_images()
+                .into_iter()
+                .map(|(service, image)| (service.to_string(), image)),
+        );
+        if self.proxy.enable {
+            images.push(("proxy".to_string(), NGINX_IMAGE.to_string()));
+        }
+        images
+    }
+}
+
+impl OrbitStack {
+    /// Checks that every image of the stack has a manifest for the platform its service runs
+    /// on, asking the registry first and falling back to the local image store.
+    pub async fn check_platforms(&self) -> Result<(), bollard::errors::Error> {
+        let checks: BTreeSet<(String, String)> = self
+            .config
+            .service_images()
+            .into_iter()
+            .map(|(service, image)| (image, self.config.platform_for(&service)))
+            .collect();
+
+        for (image, platform) in checks {
+            let available = match self.docker.inspect_registry_image(&image, None).await {
+                Ok(distribution) => distribution.platforms.iter().any(|candidate| {
+                    platform_matches(
+                        &platform,
+                        candidate.os.as_deref(),
+                        candidate.architecture.as_deref(),
+                        candidate.variant.as_deref(),
+                    )
+                }),
+                Err(registry_error) => match self.docker.inspect_image(&image).await {
+                    Ok(local) => platform_matches(
+                        &platform,
+                        local.os.as_deref(),
+                        local.architecture.as_deref(),
+                        local.variant.as_deref(),
+                    ),
+                    Err(_) => return Err(registry_error),
+                },
+            };
+            if !available {
+                return Err(invalid_config(&format!(
+                    "image {} is not published for {}",
+                    image, platform
+                )));
+            }
+        }
+        Ok(())
+    }
+}
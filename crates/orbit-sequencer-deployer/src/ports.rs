@@ -0,0 +1,146 @@
+//! Host ports published by the stack's services. They are configured per chain, so that several
+//! stacks can share a host; they are checked against each other when the configuration is
+//! validated, and against the ports of other containers when the stack starts.
+
+use crate::das::{das_host_ports, das_service, DAS_MIRROR_SERVICE};
+use crate::events::STACK_LABEL;
+use crate::explorer::ExplorerService;
+use crate::{invalid_config, HostPort, OrbitStack, OrbitStackConfig};
+use gadget_sdk::docker::bollard;
+
+/// Interface the stack's internal services are published on.
+const LOOPBACK: &str = "127.0.0.1";
+
+impl OrbitStackConfig {
+    /// Host ports the stack's services publish, by service.
+    pub fn host_ports(&self) -> Vec<(String, HostPort)> {
+        let mut ports = Vec::new();
+        let mut publish = |service: &str, host_ip: &str, host_port: u16| {
+            ports.push((
+                service.to_string(),
+                HostPort {
+                    host_ip: host_ip.to_string(),
+                    host_port,
+                },
+            ))
+        };
+
+        for host_port in self
+            .node_ports()
+            .into_iter()
+            .filter_map(|(_, publish)| publish)
+        {
+            publish("nitro-node", &host_port.host_ip, host_port.host_port);
+        }
+        if let Some(committee) = &self.das_committee {
+            for index in 0..committee.members {
+                let (rpc_port, rest_port) = das_host_ports(committee.host_port_base, index);
+                publish(&das_service(index), LOOPBACK, rpc_port);
+                publish(&das_service(index), LOOPBACK, rest_port);
+            }
+        }
+        if let Some(mirror) = &self.das_mirror {
+            publish(DAS_MIRROR_SERVICE, LOOPBACK, mirror.rest_host_port);
+        }
+        if self.explorer_enabled() {
+            if let Some(port) = self.explorer.db_host_port {
+                publish("db", LOOPBACK, port);
+            }
+            if self.service_enabled(ExplorerService::Stats) {
+                if let Some(port) = self.explorer.stats_db_host_port {
+                    publish("stats-db", LOOPBACK, port);
+                }
+            }
+        }
+        if self.proxy.enable {
+            for (_, host_port) in self.proxy_ports() {
+                publish("proxy", &self.proxy.host_ip, host_port);
+            }
+        }
+        ports
+    }
+
+    /// Checks that no two services of the stack publish the same host port.
+    pub(crate) fn validate_host_ports(&self) -> Result<(), bollard::errors::Error> {
+        let ports = self.host_ports();
+        for (i, (service, port)) in ports.iter().enumerate() {
+            if let Some((other, _)) = ports[..i].iter().find(|(_, other)| overlaps(port, other)) {
+                return Err(invalid_config(&format!(
+                    "{} and {} both publish host port {}",
+                    other, service, port.host_port
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OrbitStack {
+    /// Checks that no container outside this stack already publishes a host port the stack
+    /// needs. Containers left over from an earlier run of the same chain are replaced on start,
+    /// so they are not counted.
+    pub(crate) async fn check_host_ports(&self) -> Result<(), bollard::errors::Error> {
+        let ports = self.config.host_ports();
+        let containers = self.docker.list_containers::<String>(None).await?;
+        for container in containers {
+            let stack = container
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get(STACK_LABEL));
+            if stack == Some(&self.config.chain_name) {
+                continue;
+            }
+            for published in container.ports.iter().flatten() {
+                let Some(host_port) = published.public_port else {
+                    continue;
+                };
+                let taken = HostPort {
+                    host_ip: published.ip.clone().unwrap_or_default(),
+                    host_port,
+                };
+                if let Some((service, _)) = ports.iter().find(|(_, port)| overlaps(port, &taken)) {
+                    let name = container
+                        .names
+                        .as_ref()
+                        .and_then(|names| names.first())
+                        .map(|name| name.trim_start_matches('/').to_string())
+                        .or_else(|| container.id.clone())
+                        .unwrap_or_default();
+                    return Err(invalid_config(&format!(
+                        "host port {} of {} is already published by container {}",
+                        host_port, service, name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether two bindings claim the same port. A binding on an unspecified address claims the port
+/// on every interface.
+fn overlaps(a: &HostPort, b: &HostPort) -> bool {
+    let unspecified = |port: &HostPort| ["", "0.0.0.0", "::"].contains(&port.host_ip.as_str());
+    a.host_port == b.host_port && (a.host_ip == b.host_ip || unspecified(a) || unspecified(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(host_ip: &str, host_port: u16) -> HostPort {
+        HostPort {
+            host_ip: host_ip.to_string(),
+            host_port,
+        }
+    }
+
+    #[test]
+    fn unspecified_addresses_claim_every_interface() {
+        assert!(overlaps(&port("127.0.0.1", 80), &port("127.0.0.1", 80)));
+        assert!(overlaps(&port("127.0.0.1", 80), &port("0.0.0.0", 80)));
+        assert!(overlaps(&port("::", 80), &port("10.0.0.1", 80)));
+        assert!(!overlaps(&port("127.0.0.1", 80), &port("10.0.0.1", 80)));
+        assert!(!overlaps(&port("0.0.0.0", 80), &port("0.0.0.0", 443)));
+    }
+}
@@ -0,0 +1,408 @@
+//! Service-specific health and readiness probes. Docker only knows whether a container is
+//! running, or its healthcheck passes where one is set; probes ask the service itself: nitro
+//! nodes for their head block and sync state, Blockscout and DAS servers for their health
+//! endpoints and Redis for a `PONG`.
+//!
+//! Probes connect to the containers' addresses on the stack's network. A process running in a
+//! container of its own is attached to that network when the stack starts, so that it reaches
+//! them too.
+
+use crate::das::DAS_REST_PORT;
+use crate::node_config::DEFAULT_HTTP_PORT;
+use crate::resources::service_kind;
+use crate::OrbitStack;
+use gadget_sdk::docker::bollard::{
+    self,
+    secret::{ContainerInspectResponse, ContainerStateStatusEnum, HealthStatusEnum},
+    Docker,
+};
+use gadget_sdk::tokio::{
+    self,
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time a probe may take before the service is considered unresponsive.
+pub(crate) const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time a syncing node's head may stay unchanged before the node is considered stalled.
+const STALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+const BLOCKSCOUT_PORT: u16 = 4000;
+const REDIS_PORT: u16 = 6379;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    /// Serving and up to date.
+    Healthy,
+    /// Serving but not yet up to date, such as a node catching up with its parent chain.
+    Degraded,
+    /// Running but failing its probe.
+    Unhealthy,
+    /// Running, but its probe could not connect to it, so whether it serves is unknown.
+    Unreachable,
+    /// Not running, or not part of the stack.
+    Down,
+}
+
+/// Outcome of probing a service.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProbeStatus {
+    pub service: String,
+    pub health: Health,
+    /// Whether the service answers requests, even if it is not up to date.
+    pub ready: bool,
+    /// State of the container, if it exists.
+    pub container: Option<ContainerStateStatusEnum>,
+    /// Status of the container's Docker healthcheck, if it has one.
+    pub docker_health: Option<HealthStatusEnum>,
+    /// Times Docker restarted the container under its restart policy.
+    pub restart_count: i64,
+    /// Head block of a nitro node.
+    pub block_number: Option<u64>,
+    /// Whether a nitro node reports that it is syncing.
+    pub syncing: Option<bool>,
+    /// Time the service took to answer its probe.
+    pub latency: Option<Duration>,
+    /// Why the service is not healthy.
+    pub message: Option<String>,
+}
+
+impl ProbeStatus {
+    fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            health: Health::Down,
+            ready: false,
+            container: None,
+            docker_health: None,
+            restart_count: 0,
+            block_number: None,
+            syncing: None,
+            latency: None,
+            message: None,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health == Health::Healthy
+    }
+
+    fn fail(&mut self, health: Health, message: impl Into<String>) {
+        self.health = health;
+        self.message = Some(message.into());
+    }
+
+    /// Judges the service by its Docker healthcheck, or by its container running if it has none.
+    fn judge_by_healthcheck(&mut self) {
+        match self.docker_health {
+            None | Some(HealthStatusEnum::EMPTY) | Some(HealthStatusEnum::HEALTHY) => {
+                self.health = Health::Healthy;
+                self.ready = true;
+                self.message = None;
+            }
+            Some(HealthStatusEnum::STARTING) => {
+                self.fail(Health::Degraded, "healthcheck is starting")
+            }
+            Some(_) => self.fail(Health::Unhealthy, "healthcheck is failing"),
+        }
+    }
+
+    /// Records that the service could not be connected to. A service with a Docker healthcheck
+    /// is judged by it instead; one without is reported unreachable rather than unhealthy, as the
+    /// probe cannot tell a service that is down from one it has no route to.
+    fn unreachable(&mut self, message: impl Into<String>) {
+        match self.docker_health {
+            None | Some(HealthStatusEnum::EMPTY) => self.fail(Health::Unreachable, message),
+            Some(_) => self.judge_by_healthcheck(),
+        }
+    }
+}
+
+/// What is asked of a service to probe it.
+enum Probe {
+    Nitro,
+    Http(u16, &'static str),
+    Redis,
+}
+
+/// Probe of a service. Services without one are judged by their Docker healthcheck if they
+/// have one, else by whether they are running.
+fn probe_for(service: &str) -> Option<Probe> {
+    match service_kind(service) {
+        "nitro-node" | "nitro-validator" => Some(Probe::Nitro),
+        "backend" => Some(Probe::Http(BLOCKSCOUT_PORT, "/api/health")),
+        "das-server" | "das-mirror" => Some(Probe::Http(DAS_REST_PORT, "/health")),
+        "redis_db" => Some(Probe::Redis),
+        _ => None,
+    }
+}
+
+/// Sends a request over a fresh connection and returns the response's status code and body.
+pub(crate) async fn http_request(
+    addr: &str,
+    method: &str,
+    path: &str,
+    body: Option<&str>,
+) -> std::io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect(addr).await?;
+    let mut request = format!(
+        "{} {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n",
+        method, path, addr
+    );
+    if let Some(body) = body {
+        request.push_str(&format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+    } else {
+        request.push_str("\r\n");
+    }
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    let response = String::from_utf8_lossy(&response);
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed response");
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(invalid)?;
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(invalid)?;
+    Ok((status, body.to_string()))
+}
+
+/// Calls a JSON-RPC method without parameters and returns its result.
+async fn rpc_call(addr: &str, method: &str) -> std::io::Result<Value> {
+    let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": []});
+    let (status, body) = http_request(addr, "POST", "/", Some(&request.to_string())).await?;
+    let mut response: Value = serde_json::from_str(&body).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} returned {}: {}", method, status, e),
+        )
+    })?;
+    if let Some(error) = response.get("error") {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("{} failed: {}", method, error),
+        ));
+    }
+    Ok(response["result"].take())
+}
+
+async fn redis_ping(addr: &str) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(b"PING\r\n").await?;
+    let mut reply = [0u8; 7];
+    stream.read_exact(&mut reply).await?;
+    if &reply != b"+PONG\r\n" {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unexpected reply {:?}", String::from_utf8_lossy(&reply)),
+        ));
+    }
+    Ok(())
+}
+
+/// Address of a container on the stack's network, which the stack's services are reached on.
+fn network_ip(inspect: ContainerInspectResponse, network_name: &str) -> Option<String> {
+    inspect
+        .network_settings
+        .and_then(|settings| settings.networks)
+        .and_then(|mut networks| networks.remove(network_name))
+        .and_then(|network| network.ip_address)
+        .filter(|ip| !ip.is_empty())
+}
+
+impl OrbitStack {
+    /// Snapshot of the stack's services to probe them with, without holding on to the stack.
+    pub fn prober(&self) -> Prober {
+        Prober {
+            docker: self.docker.clone(),
+            containers: self.containers.clone(),
+            network_name: self.config.network_name(),
+            heads: self.heads.clone(),
+        }
+    }
+
+    /// Probes a service. See [`Prober::probe`].
+    pub async fn probe(&self, service: &str) -> Result<ProbeStatus, bollard::errors::Error> {
+        self.prober().probe(service).await
+    }
+
+    /// Probes the services the stack has started.
+    pub async fn probe_all(&self) -> Result<Vec<ProbeStatus>, bollard::errors::Error> {
+        self.prober().probe_all().await
+    }
+}
+
+/// Probes the services of a stack as they were when [`OrbitStack::prober`] was called. The heads
+/// of nitro nodes are shared with the stack, so stalls are detected across snapshots.
+#[derive(Clone)]
+pub struct Prober {
+    docker: Arc<Docker>,
+    containers: HashMap<String, String>,
+    network_name: String,
+    heads: Arc<Mutex<HashMap<String, (u64, Instant)>>>,
+}
+
+impl Prober {
+    /// Services of the stack, in the order they are probed.
+    pub(crate) fn services(&self) -> Vec<&String> {
+        let mut services: Vec<&String> = self.containers.keys().collect();
+        services.sort();
+        services
+    }
+
+    /// Address of a running service on the stack's network.
+    pub(crate) async fn service_ip(
+        &self,
+        service: &str,
+    ) -> Result<Option<String>, bollard::errors::Error> {
+        match self.containers.get(service) {
+            Some(container_id) => Ok(network_ip(
+                self.docker.inspect_container(container_id, None).await?,
+                &self.network_name,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Probes a service, combining the state of its container with a check of the service
+    /// itself. Fails only if Docker cannot be reached; a failing service is reported in the
+    /// returned status.
+    pub async fn probe(&self, service: &str) -> Result<ProbeStatus, bollard::errors::Error> {
+        let mut status = ProbeStatus::new(service);
+        let Some(container_id) = self.containers.get(service) else {
+            status.fail(Health::Down, "service is not part of the stack");
+            return Ok(status);
+        };
+
+        let inspect = self.docker.inspect_container(container_id, None).await?;
+        status.restart_count = inspect.restart_count.unwrap_or_default();
+        let state = inspect.state.unwrap_or_default();
+        status.container = state.status;
+        status.docker_health = state.health.and_then(|health| health.status);
+        if !state.running.unwrap_or(false) {
+            status.fail(Health::Down, "container is not running");
+            return Ok(status);
+        }
+
+        let Some(probe) = probe_for(service) else {
+            status.judge_by_healthcheck();
+            return Ok(status);
+        };
+
+        let Some(ip) = network_ip(inspect, &self.network_name) else {
+            status.unreachable(format!("container has no address on {}", self.network_name));
+            return Ok(status);
+        };
+
+        let start = Instant::now();
+        let result = match probe {
+            Probe::Nitro => {
+                let addr = format!("{}:{}", ip, DEFAULT_HTTP_PORT);
+                tokio::time::timeout(PROBE_TIMEOUT, self.probe_nitro(&addr, &mut status)).await
+            }
+            Probe::Http(port, path) => {
+                let addr = format!("{}:{}", ip, port);
+                tokio::time::timeout(PROBE_TIMEOUT, async {
+                    match http_request(&addr, "GET", path, None).await? {
+                        (200, _) => Ok(()),
+                        (code, body) => Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            format!("{} returned {}: {}", path, code, body.trim()),
+                        )),
+                    }
+                })
+                .await
+            }
+            Probe::Redis => {
+                let addr = format!("{}:{}", ip, REDIS_PORT);
+                tokio::time::timeout(PROBE_TIMEOUT, redis_ping(&addr)).await
+            }
+        };
+        status.latency = Some(start.elapsed());
+
+        match result {
+            Ok(Ok(())) => {
+                status.ready = true;
+                if status.message.is_none() {
+                    status.health = Health::Healthy;
+                }
+            }
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                status.unreachable(e.to_string())
+            }
+            Ok(Err(e)) => status.fail(Health::Unhealthy, e.to_string()),
+            Err(_) => status.fail(
+                Health::Unhealthy,
+                format!("no answer within {:?}", PROBE_TIMEOUT),
+            ),
+        }
+        Ok(status)
+    }
+
+    /// Probes the services the stack has started.
+    pub async fn probe_all(&self) -> Result<Vec<ProbeStatus>, bollard::errors::Error> {
+        let mut statuses = Vec::new();
+        for service in self.services() {
+            statuses.push(self.probe(service).await?);
+        }
+        Ok(statuses)
+    }
+
+    /// Reads a node's head block and sync state. A syncing node whose head has not moved for
+    /// `STALL_TIMEOUT` is reported as unhealthy, one that is catching up as degraded.
+    async fn probe_nitro(&self, addr: &str, status: &mut ProbeStatus) -> std::io::Result<()> {
+        let block = rpc_call(addr, "eth_blockNumber").await?;
+        let block_number = block
+            .as_str()
+            .and_then(|hex| u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok())
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid block number {}", block),
+                )
+            })?;
+        // `false` once in sync, an object describing the progress otherwise
+        let syncing = rpc_call(addr, "eth_syncing").await? != Value::Bool(false);
+        status.block_number = Some(block_number);
+        status.syncing = Some(syncing);
+
+        let now = Instant::now();
+        let mut heads = self.heads.lock().unwrap_or_else(|e| e.into_inner());
+        let since = match heads.get(&status.service) {
+            Some(&(last, since)) if last == block_number => since,
+            _ => now,
+        };
+        heads.insert(status.service.clone(), (block_number, since));
+
+        if syncing {
+            if now.duration_since(since) >= STALL_TIMEOUT {
+                status.fail(
+                    Health::Unhealthy,
+                    format!(
+                        "syncing but stuck at block {} for {:?}",
+                        block_number,
+                        now.duration_since(since)
+                    ),
+                );
+            } else {
+                status.fail(
+                    Health::Degraded,
+                    format!("syncing at block {}", block_number),
+                );
+            }
+        }
+        Ok(())
+    }
+}
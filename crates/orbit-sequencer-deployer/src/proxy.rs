@@ -0,0 +1,406 @@
+//! nginx proxy in front of the stack. Its configuration is rendered from the services that are
+//! enabled: the chain RPC and sequencer feed, the explorer and its stats and visualizer APIs.
+
+use crate::explorer::ExplorerService;
+use crate::node_config::{DEFAULT_FEED_PORT, DEFAULT_HTTP_PORT, DEFAULT_WS_PORT};
+use crate::{invalid_config, OrbitStack, OrbitStackConfig};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::Config,
+    models::{HostConfig, PortBinding},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+pub(crate) const NGINX_IMAGE: &str = "nginx";
+
+// Paths inside the proxy container
+const NGINX_CONF_FILE: &str = "/etc/nginx/conf.d/default.conf";
+const NGINX_CERT_DIR: &str = "/etc/nginx/certs";
+
+// Ports the proxy listens on inside its container
+const HTTP_PORT: u16 = 80;
+const HTTPS_PORT: u16 = 443;
+const STATS_PORT: u16 = 8080;
+const VISUALIZER_PORT: u16 = 8081;
+
+// Docker's embedded DNS, re-queried so that upstreams recreated with a new address are found.
+// nginx only does so for upstreams given as variables.
+const RESOLVER: &str = "resolver 127.0.0.11 valid=10s ipv6=off;\n\n";
+
+const HTTPS_REDIRECT: &str = "\
+server {
+    listen 80 default_server;
+    return 301 https://$host$request_uri;
+}
+
+";
+
+// Directives of every location, then of websocket and CORS enabled ones
+const PROXY_HEADERS: &[&str] = &[
+    "proxy_http_version 1.1;",
+    "proxy_set_header Host $host;",
+    "proxy_set_header X-Real-IP $remote_addr;",
+    "proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;",
+    "proxy_set_header X-Forwarded-Proto $scheme;",
+];
+const WEBSOCKET_HEADERS: &[&str] = &[
+    "proxy_set_header Upgrade $http_upgrade;",
+    "proxy_set_header Connection \"upgrade\";",
+    "proxy_read_timeout 1h;",
+];
+const CORS_HEADERS: &[&str] = &[
+    "add_header Access-Control-Allow-Origin * always;",
+    "add_header Access-Control-Allow-Methods \"GET, POST, OPTIONS\" always;",
+];
+
+/// How the proxy exposes the stack.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub enable: bool,
+    /// Host interface the proxy ports are published on.
+    pub host_ip: String,
+    pub http_port: u16,
+    pub https_port: u16,
+    /// Hostname the chain RPC is served at. Without one, it is served under `/rpc` of the
+    /// explorer host. WebSocket RPC and the feed are served under `/ws` and `/feed` either way.
+    pub rpc_domain: Option<String>,
+    /// Hostname of the stats API. Without one, it is served on port 8080 of the explorer host.
+    pub stats_domain: Option<String>,
+    /// Hostname of the visualizer API. Without one, it is served on port 8081 of the explorer
+    /// host.
+    pub visualizer_domain: Option<String>,
+    /// Terminates TLS with the given certificate, redirecting plain HTTP to HTTPS.
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            host_ip: "0.0.0.0".to_string(),
+            http_port: 80,
+            https_port: 443,
+            rpc_domain: None,
+            stats_domain: None,
+            visualizer_domain: None,
+            tls: None,
+        }
+    }
+}
+
+/// PEM files of the certificate chain and its private key, on the host.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TlsConfig {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+}
+
+/// A location proxied to a service of the stack.
+struct Location {
+    path: &'static str,
+    /// URL requests are passed to. Given with a path, it replaces the request's path, so only
+    /// exact-match locations may have one.
+    upstream: String,
+    websocket: bool,
+}
+
+impl Location {
+    fn new(path: &'static str, upstream: String) -> Self {
+        Self {
+            path,
+            upstream,
+            websocket: false,
+        }
+    }
+
+    fn websocket(mut self) -> Self {
+        self.websocket = true;
+        self
+    }
+}
+
+/// A virtual server, matched on its port and, if set, its hostname.
+struct Server {
+    name: Option<String>,
+    port: u16,
+    cors: bool,
+    locations: Vec<Location>,
+}
+
+impl OrbitStackConfig {
+    /// Hostname the explorer is served at.
+    fn explorer_host(&self) -> &str {
+        &self.explorer.public_host
+    }
+
+    /// Public URL of the stats API, as used by the explorer frontend.
+    pub(crate) fn stats_api_url(&self) -> String {
+        match &self.proxy.stats_domain {
+            Some(domain) => format!("{}://{}", self.public_scheme(), domain),
+            None => format!(
+                "{}://{}:{}",
+                self.public_scheme(),
+                self.explorer_host(),
+                STATS_PORT
+            ),
+        }
+    }
+
+    /// Public URL of the visualizer API, as used by the explorer frontend.
+    pub(crate) fn visualizer_api_url(&self) -> String {
+        match &self.proxy.visualizer_domain {
+            Some(domain) => format!("{}://{}", self.public_scheme(), domain),
+            None => format!(
+                "{}://{}:{}",
+                self.public_scheme(),
+                self.explorer_host(),
+                VISUALIZER_PORT
+            ),
+        }
+    }
+
+    /// Whether public URLs are served over HTTPS.
+    pub(crate) fn public_https(&self) -> bool {
+        self.explorer.https || self.proxy.tls.is_some()
+    }
+
+    fn public_scheme(&self) -> &'static str {
+        if self.public_https() {
+            "https"
+        } else {
+            "http"
+        }
+    }
+
+    pub(crate) fn validate_proxy(&self) -> Result<(), bollard::errors::Error> {
+        if let Some(tls) = self.proxy.tls.as_ref().filter(|_| self.proxy.enable) {
+            for path in [&tls.certificate, &tls.private_key] {
+                if !path.is_file() {
+                    return Err(invalid_config(&format!(
+                        "TLS file {} does not exist",
+                        path.display()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ports nginx listens on, each with the host port it is published on.
+    pub(crate) fn proxy_ports(&self) -> Vec<(u16, u16)> {
+        let mut ports = vec![(HTTP_PORT, self.proxy.http_port)];
+        if self.proxy.tls.is_some() {
+            ports.push((HTTPS_PORT, self.proxy.https_port));
+        }
+        for server in self.proxy_servers() {
+            if ![HTTP_PORT, HTTPS_PORT].contains(&server.port) {
+                ports.push((server.port, server.port));
+            }
+        }
+        ports
+    }
+
+    /// Virtual servers for the services that are enabled.
+    fn proxy_servers(&self) -> Vec<Server> {
+        let main_port = if self.proxy.tls.is_some() {
+            HTTPS_PORT
+        } else {
+            HTTP_PORT
+        };
+        let mut servers = Vec::new();
+        let rpc_upstream = format!("http://nitro-node:{}", DEFAULT_HTTP_PORT);
+        // WebSocket RPC and the feed are served at the root of their own ports on the node
+        let mut streams = Vec::new();
+        if self.endpoints.ws.enable {
+            streams.push(
+                Location::new("= /ws", format!("http://nitro-node:{}/", DEFAULT_WS_PORT))
+                    .websocket(),
+            );
+        }
+        if self.feed_enabled() {
+            streams.push(
+                Location::new(
+                    "= /feed",
+                    format!("http://nitro-node:{}/", DEFAULT_FEED_PORT),
+                )
+                .websocket(),
+            );
+        }
+
+        let mut explorer = Vec::new();
+        if self.explorer_enabled() {
+            explorer.push(Location::new("/", "http://frontend:3000".to_string()));
+            for path in ["/api", "/sitemap.xml", "/auth"] {
+                explorer.push(Location::new(path, "http://backend:4000".to_string()));
+            }
+            explorer.push(Location::new("/socket", "http://backend:4000".to_string()).websocket());
+        }
+
+        let rpc_server = match &self.proxy.rpc_domain {
+            Some(domain) => Some(Server {
+                name: Some(domain.clone()),
+                port: main_port,
+                cors: false,
+                locations: std::iter::once(Location::new("/", rpc_upstream))
+                    .chain(streams)
+                    .collect(),
+            }),
+            None => {
+                explorer.push(Location::new("= /rpc", format!("{}/", rpc_upstream)));
+                explorer.extend(streams);
+                None
+            }
+        };
+
+        // The first server on a port answers requests for unknown hosts
+        if !explorer.is_empty() {
+            servers.push(Server {
+                name: self
+                    .explorer_enabled()
+                    .then(|| self.explorer_host().to_string()),
+                port: main_port,
+                cors: false,
+                locations: explorer,
+            });
+        }
+        servers.extend(rpc_server);
+
+        for (service, domain, port, upstream) in [
+            (
+                ExplorerService::Stats,
+                &self.proxy.stats_domain,
+                STATS_PORT,
+                "http://stats:8050",
+            ),
+            (
+                ExplorerService::Visualizer,
+                &self.proxy.visualizer_domain,
+                VISUALIZER_PORT,
+                "http://visualizer:8050",
+            ),
+        ] {
+            if self.service_enabled(service) {
+                servers.push(Server {
+                    name: domain.clone(),
+                    port: if domain.is_some() { main_port } else { port },
+                    cors: true,
+                    locations: vec![Location::new("/", upstream.to_string())],
+                });
+            }
+        }
+
+        servers
+    }
+
+    /// Renders the nginx configuration of the proxy.
+    pub fn proxy_config(&self) -> String {
+        let tls = self.proxy.tls.is_some();
+        let mut conf = RESOLVER.to_string();
+
+        if tls {
+            conf.push_str(HTTPS_REDIRECT);
+        }
+
+        for server in self.proxy_servers() {
+            let _ = writeln!(conf, "server {{");
+            let ssl = if tls { " ssl" } else { "" };
+            let _ = writeln!(conf, "    listen {}{};", server.port, ssl);
+            if let Some(name) = &server.name {
+                let _ = writeln!(conf, "    server_name {};", name);
+            }
+            if tls {
+                let _ = writeln!(
+                    conf,
+                    "    ssl_certificate {}/fullchain.pem;",
+                    NGINX_CERT_DIR
+                );
+                let _ = writeln!(
+                    conf,
+                    "    ssl_certificate_key {}/privkey.pem;",
+                    NGINX_CERT_DIR
+                );
+            }
+            for location in &server.locations {
+                let _ = writeln!(conf, "\n    location {} {{", location.path);
+                let _ = writeln!(conf, "        set $upstream {};", location.upstream);
+                let _ = writeln!(conf, "        proxy_pass $upstream;");
+                let mut directives = PROXY_HEADERS.to_vec();
+                if location.websocket {
+                    directives.extend(WEBSOCKET_HEADERS);
+                }
+                if server.cors {
+                    directives.extend(CORS_HEADERS);
+                }
+                for directive in directives {
+                    let _ = writeln!(conf, "        {}", directive);
+                }
+                let _ = writeln!(conf, "    }}");
+            }
+            let _ = writeln!(conf, "}}\n");
+        }
+
+        conf
+    }
+}
+
+impl OrbitStack {
+    /// Writes the proxy configuration and starts nginx, publishing the ports it listens on.
+    pub(crate) async fn start_proxy(&mut self) -> Result<(), bollard::errors::Error> {
+        if !self.config.proxy.enable {
+            return Ok(());
+        }
+
+        let proxy_dir = self.config.data_dir.join("proxy");
+        std::fs::create_dir_all(&proxy_dir)?;
+        std::fs::write(proxy_dir.join("default.conf"), self.config.proxy_config())?;
+
+        let proxy = &self.config.proxy;
+        let mut binds = vec![format!(
+            "{}:{}:ro",
+            proxy_dir.join("default.conf").display(),
+            NGINX_CONF_FILE
+        )];
+        if let Some(tls) = &proxy.tls {
+            binds.push(format!(
+                "{}:{}/fullchain.pem:ro",
+                tls.certificate.display(),
+                NGINX_CERT_DIR
+            ));
+            binds.push(format!(
+                "{}:{}/privkey.pem:ro",
+                tls.private_key.display(),
+                NGINX_CERT_DIR
+            ));
+        }
+
+        let config = Config {
+            image: Some(NGINX_IMAGE.to_string()),
+            host_config: Some(HostConfig {
+                binds: Some(binds),
+                port_bindings: Some(
+                    self.config
+                        .proxy_ports()
+                        .into_iter()
+                        .map(|(container_port, host_port)| {
+                            (
+                                format!("{}/tcp", container_port),
+                                Some(vec![PortBinding {
+                                    host_ip: Some(proxy.host_ip.clone()),
+                                    host_port: Some(host_port.to_string()),
+                                }]),
+                            )
+                        })
+                        .collect::<HashMap<_, _>>(),
+                ),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        self.create_and_start_container("proxy", config).await
+    }
+}
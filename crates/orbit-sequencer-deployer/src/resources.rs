@@ -0,0 +1,130 @@
+//! Resource limits and restart policies of the stack's containers, applied through their
+//! `HostConfig`.
+
+use crate::OrbitStackConfig;
+use gadget_sdk::docker::bollard::models::{
+    HostConfig, ResourcesUlimits, RestartPolicy as DockerRestartPolicy, RestartPolicyNameEnum,
+};
+use serde::{Deserialize, Serialize};
+
+const GIB: i64 = 1 << 30;
+const MIB: i64 = 1 << 20;
+
+/// Limits and restart policy of a service. Unset fields keep the stack's default for the
+/// service.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceResources {
+    /// Memory limit, in bytes.
+    pub memory: Option<i64>,
+    /// CPU limit, in CPUs.
+    pub cpus: Option<f64>,
+    /// Limit on open files.
+    pub nofile: Option<i64>,
+    pub restart: Option<RestartPolicy>,
+    /// Seconds the service is given to exit on shutdown before it is killed.
+    pub stop_timeout_secs: Option<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartPolicy {
+    No,
+    Always,
+    UnlessStopped,
+    OnFailure { max_retries: i64 },
+}
+
+impl ServiceResources {
+    /// Defaults of a service, by its name with any member index stripped. Long-running services
+    /// restart unless stopped, and only the services that may grow unbounded are capped. Nodes
+    /// get long to flush their state on shutdown, stateless services little.
+    fn default_for(kind: &str) -> Option<Self> {
+        let (memory, cpus, nofile, stop_timeout_secs) = match kind {
+            "nitro-node" | "nitro-validator" => (None, None, Some(65536), 300),
+            "das-server" | "das-mirror" => (Some(2 * GIB), None, Some(65536), 60),
+            "db" | "stats-db" => (Some(4 * GIB), Some(2.0), None, 60),
+            "redis_db" => (Some(512 * MIB), None, None, 30),
+            "backend" => (Some(4 * GIB), Some(2.0), None, 60),
+            "stats" | "visualizer" | "sig-provider" | "smart-contract-verifier" => {
+                (Some(GIB), Some(1.0), None, 30)
+            }
+            "frontend" => (Some(GIB), Some(1.0), None, 10),
+            "proxy" => (Some(256 * MIB), None, None, 10),
+            _ => return None,
+        };
+        Some(Self {
+            memory,
+            cpus,
+            nofile,
+            restart: Some(RestartPolicy::UnlessStopped),
+            stop_timeout_secs: Some(stop_timeout_secs),
+        })
+    }
+
+    /// Fields set in `self` win over those of `defaults`.
+    fn or(self, defaults: Self) -> Self {
+        Self {
+            memory: self.memory.or(defaults.memory),
+            cpus: self.cpus.or(defaults.cpus),
+            nofile: self.nofile.or(defaults.nofile),
+            restart: self.restart.or(defaults.restart),
+            stop_timeout_secs: self.stop_timeout_secs.or(defaults.stop_timeout_secs),
+        }
+    }
+
+    pub(crate) fn apply(&self, host_config: &mut HostConfig) {
+        if let Some(memory) = self.memory {
+            host_config.memory = Some(memory);
+        }
+        if let Some(cpus) = self.cpus {
+            host_config.nano_cpus = Some((cpus * 1e9) as i64);
+        }
+        if let Some(nofile) = self.nofile {
+            host_config.ulimits = Some(vec![ResourcesUlimits {
+                name: Some("nofile".to_string()),
+                soft: Some(nofile),
+                hard: Some(nofile),
+            }]);
+        }
+        if let Some(restart) = self.restart {
+            let (name, maximum_retry_count) = match restart {
+                RestartPolicy::No => (RestartPolicyNameEnum::NO, None),
+                RestartPolicy::Always => (RestartPolicyNameEnum::ALWAYS, None),
+                RestartPolicy::UnlessStopped => (RestartPolicyNameEnum::UNLESS_STOPPED, None),
+                RestartPolicy::OnFailure { max_retries } => {
+                    (RestartPolicyNameEnum::ON_FAILURE, Some(max_retries))
+                }
+            };
+            host_config.restart_policy = Some(DockerRestartPolicy {
+                name: Some(name),
+                maximum_retry_count,
+            });
+        }
+    }
+}
+
+/// Name of a service with the index of committee members and validators stripped, so that
+/// `das-server-1` is configured as `das-server`.
+pub(crate) fn service_kind(service: &str) -> &str {
+    match service.rsplit_once('-') {
+        Some((kind, index)) if index.parse::<usize>().is_ok() => kind,
+        _ => service,
+    }
+}
+
+impl OrbitStackConfig {
+    /// Resources of a service: its own entry in `resources`, then the entry of its kind, then
+    /// the stack's defaults.
+    pub fn service_resources(&self, service: &str) -> ServiceResources {
+        let kind = service_kind(service);
+        let configured = [service, kind]
+            .into_iter()
+            .filter_map(|name| self.resources.get(name).cloned())
+            .fold(ServiceResources::default(), ServiceResources::or);
+        match ServiceResources::default_for(kind) {
+            Some(defaults) => configured.or(defaults),
+            None => configured,
+        }
+    }
+}
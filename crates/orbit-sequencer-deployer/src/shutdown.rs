@@ -0,0 +1,245 @@
+//! Ordered shutdown and teardown of the stack. Services stop in the reverse of the order they
+//! depend on each other, so nothing loses a dependency while it still serves; services of the
+//! same tier stop together.
+
+use crate::events::{SERVICE_LABEL, STACK_LABEL};
+use crate::resources::service_kind;
+use crate::{OrbitStack, NITRO_NODE_IMAGE};
+use gadget_sdk::docker::bollard::{
+    self,
+    container::{Config, ListContainersOptions, RemoveContainerOptions, StopContainerOptions},
+    models::HostConfig,
+};
+use gadget_sdk::{info, tokio::task::JoinSet, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Seconds given to a service without a configured stop timeout.
+const DEFAULT_STOP_TIMEOUT_SECS: i64 = 30;
+
+/// Where the purge job mounts the data directory it empties.
+const PURGE_DIR: &str = "/purge";
+
+/// Start order of a service, by its kind. Services outside the stack's own set depend on
+/// nothing known and stop first.
+fn tier(kind: &str) -> u8 {
+    match kind {
+        "db" | "stats-db" | "redis_db" => 0,
+        "das-server" | "das-mirror" => 1,
+        "nitro-node" => 2,
+        "nitro-validator" => 3,
+        "backend" | "stats" | "visualizer" | "sig-provider" | "smart-contract-verifier" => 4,
+        "frontend" => 5,
+        "proxy" => 6,
+        _ => u8::MAX,
+    }
+}
+
+impl OrbitStack {
+    /// Stops every service in reverse dependency order, each within its stop timeout. A failure
+    /// does not hold back the remaining services; the first one is returned once all were tried.
+    pub async fn stop_all(&mut self) -> Result<(), bollard::errors::Error> {
+        let mut tiers: BTreeMap<u8, Vec<(String, String, i64)>> = BTreeMap::new();
+        for (service, container_id) in &self.containers {
+            let timeout = self
+                .config
+                .service_resources(service)
+                .stop_timeout_secs
+                .unwrap_or(DEFAULT_STOP_TIMEOUT_SECS);
+            tiers.entry(tier(service_kind(service))).or_default().push((
+                service.clone(),
+                container_id.clone(),
+                timeout,
+            ));
+        }
+
+        let mut first_error = None;
+        for (_, services) in tiers.into_iter().rev() {
+            let mut stopping = JoinSet::new();
+            for (service, container_id, timeout) in services {
+                let docker = Arc::clone(&self.docker);
+                stopping.spawn(async move {
+                    info!("Stopping container for service: {} ({}s)", service, timeout);
+                    let result = docker
+                        .stop_container(&container_id, Some(StopContainerOptions { t: timeout }))
+                        .await;
+                    (service, result)
+                });
+            }
+            while let Some(stopped) = stopping.join_next().await {
+                let error = match stopped {
+                    // Already stopped
+                    Ok((_, Ok(())))
+                    | Ok((
+                        _,
+                        Err(bollard::errors::Error::DockerResponseServerError {
+                            status_code: 304,
+                            ..
+                        }),
+                    )) => continue,
+                    Ok((service, Err(e))) => {
+                        warn!("Failed to stop {}: {}", service, e);
+                        e
+                    }
+                    Err(e) => bollard::errors::Error::IOError {
+                        err: std::io::Error::new(std::io::ErrorKind::Other, e),
+                    },
+                };
+                first_error.get_or_insert(error);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Stops and removes every container of the stack, including any left over from earlier
+    /// runs, along with their anonymous volumes, then removes the stack's network and stops
+    /// watching its events. Every step is attempted; the failures are reported together at the
+    /// end.
+    pub async fn cleanup(&mut self) -> Result<(), bollard::errors::Error> {
+        let mut errors = Vec::new();
+        if let Err(e) = self.stop_all().await {
+            errors.push(format!("stopping services: {}", e));
+        }
+
+        let mut containers = std::mem::take(&mut self.containers);
+        match self.leftover_containers().await {
+            Ok(leftovers) => {
+                for (service, container_id) in leftovers {
+                    containers.entry(service).or_insert(container_id);
+                }
+            }
+            Err(e) => errors.push(format!("listing containers: {}", e)),
+        }
+        for (service, container_id) in containers {
+            info!("Removing container for service: {}", service);
+            let removed = self
+                .docker
+                .remove_container(
+                    &container_id,
+                    Some(RemoveContainerOptions {
+                        force: true,
+                        v: true,
+                        ..Default::default()
+                    }),
+                )
+                .await;
+            match removed {
+                Ok(())
+                | Err(bollard::errors::Error::DockerResponseServerError {
+                    status_code: 404, ..
+                }) => {}
+                Err(e) => {
+                    errors.push(format!("removing {}: {}", service, e));
+                    self.containers.insert(service, container_id);
+                }
+            }
+        }
+
+        if let Err(e) = self.leave_network().await {
+            errors.push(format!("leaving network: {}", e));
+        }
+        let network_name = self.config.network_name();
+        match self.docker.remove_network(&network_name).await {
+            Ok(())
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => {}
+            Err(e) => errors.push(format!("removing network {}: {}", network_name, e)),
+        }
+        // Nothing of the stack is left to watch
+        self.stop_watching_events();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(bollard::errors::Error::IOError {
+                err: std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!(
+                        "Cleanup of {} failed: {}",
+                        self.config.chain_name,
+                        errors.join("; ")
+                    ),
+                ),
+            })
+        }
+    }
+
+    /// Tears the stack down like [`OrbitStack::cleanup`] and deletes its data directory, losing
+    /// the chain's state for good. `confirm_chain_name` must name the stack's chain. The data
+    /// is kept if the teardown failed, as a container left behind may still be writing to it.
+    ///
+    /// The services write their data as the users of their images, so it is deleted by a job
+    /// running as root; only the emptied directory is removed by the blueprint itself.
+    pub async fn purge(&mut self, confirm_chain_name: &str) -> Result<(), bollard::errors::Error> {
+        if confirm_chain_name != self.config.chain_name {
+            return Err(bollard::errors::Error::IOError {
+                err: std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Purge of {} not confirmed, got {:?}",
+                        self.config.chain_name, confirm_chain_name
+                    ),
+                ),
+            });
+        }
+
+        self.cleanup().await?;
+        warn!(
+            "Purging data of {} at {}",
+            self.config.chain_name,
+            self.config.data_dir.display()
+        );
+        // Binding a missing directory would have Docker create it, owned by root
+        if self.config.data_dir.exists() {
+            let config = Config {
+                image: Some(NITRO_NODE_IMAGE.to_string()),
+                user: Some("root".to_string()),
+                entrypoint: Some(vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    format!("find {} -mindepth 1 -delete", PURGE_DIR),
+                ]),
+                host_config: Some(HostConfig {
+                    binds: Some(vec![format!(
+                        "{}:{}",
+                        self.config.data_dir.display(),
+                        PURGE_DIR
+                    )]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            self.run_to_completion("purge", config).await?;
+        }
+        match std::fs::remove_dir(&self.config.data_dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(bollard::errors::Error::IOError { err: e })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Containers labelled as services of this stack, tracked or not, by service.
+    async fn leftover_containers(&self) -> Result<HashMap<String, String>, bollard::errors::Error> {
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![format!("{}={}", STACK_LABEL, self.config.chain_name)],
+        )]);
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await?;
+        Ok(containers
+            .into_iter()
+            .filter_map(|container| {
+                let service = container.labels?.remove(SERVICE_LABEL)?;
+                Some((service, container.id?))
+            })
+            .collect())
+    }
+}
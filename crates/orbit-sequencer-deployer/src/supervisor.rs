@@ -0,0 +1,308 @@
+//! Supervision of a running stack: services are probed on an interval and restarted when they
+//! are down or unhealthy, backing off exponentially between attempts and escalating to the
+//! operator once restarts keep failing.
+
+use crate::probe::{Health, ProbeStatus};
+use crate::resources::service_kind;
+use crate::OrbitStack;
+use gadget_sdk::docker::bollard;
+use gadget_sdk::tokio::{
+    self,
+    sync::{broadcast, Mutex},
+    task::JoinHandle,
+};
+use gadget_sdk::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Events buffered for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 256;
+
+/// How the stack is supervised once started.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SupervisorConfig {
+    pub enable: bool,
+    /// Seconds between two rounds of probes.
+    pub interval_secs: u64,
+    /// Seconds to wait before the first restart of a failing service, doubled on each further
+    /// attempt.
+    pub initial_backoff_secs: u64,
+    pub max_backoff_secs: u64,
+    /// Consecutive restarts after which a service is left alone and escalated.
+    pub max_restarts: u32,
+    /// Services that are never restarted, by name or kind of service.
+    pub exclude: Vec<String>,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            interval_secs: 30,
+            initial_backoff_secs: 10,
+            max_backoff_secs: 600,
+            max_restarts: 5,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl SupervisorConfig {
+    fn backoff(&self, restarts: u32) -> Duration {
+        let secs = self
+            .initial_backoff_secs
+            .saturating_mul(2u64.saturating_pow(restarts))
+            .min(self.max_backoff_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SupervisorEvent {
+    /// A service failed its probe.
+    Unhealthy {
+        status: ProbeStatus,
+    },
+    /// A failing service is being restarted, for the `attempt`-th time in a row.
+    Restarting {
+        service: String,
+        attempt: u32,
+    },
+    RestartFailed {
+        service: String,
+        error: String,
+    },
+    /// A service that had failed passes its probe again.
+    Recovered {
+        service: String,
+        restarts: u32,
+    },
+    /// A service kept failing after `restarts` restarts and is no longer restarted until it
+    /// recovers on its own or is restarted by the operator.
+    Escalated {
+        service: String,
+        restarts: u32,
+    },
+}
+
+/// Restart history of a failing service.
+#[derive(Debug)]
+struct Failure {
+    restarts: u32,
+    next_restart: Instant,
+    escalated: bool,
+}
+
+/// What the supervisor remembers between rounds.
+#[derive(Debug, Default)]
+pub struct SupervisorState {
+    failures: HashMap<String, Failure>,
+}
+
+/// Handle of a supervisor running in the background. Dropping it leaves the supervisor
+/// running; [`Supervisor::stop`] ends it.
+pub struct Supervisor {
+    events: broadcast::Sender<SupervisorEvent>,
+    handle: JoinHandle<()>,
+}
+
+impl Supervisor {
+    /// Receives the events emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<SupervisorEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn stop(self) {
+        self.handle.abort();
+    }
+}
+
+impl SupervisorState {
+    /// Judges a round of probes: records the failures and recoveries of the services and picks
+    /// those that are down or unhealthy and due for a restart. Degraded services, such as
+    /// syncing nodes, and unreachable ones, whose state is unknown, are left alone.
+    fn assess(
+        &mut self,
+        config: &SupervisorConfig,
+        statuses: Vec<ProbeStatus>,
+        events: &mut Vec<SupervisorEvent>,
+    ) -> Vec<String> {
+        let mut restarts = Vec::new();
+
+        for status in statuses {
+            let service = status.service.clone();
+            let kind = service_kind(&service);
+            if config
+                .exclude
+                .iter()
+                .any(|excluded| *excluded == service || excluded == kind)
+            {
+                continue;
+            }
+            if status.health == Health::Unreachable {
+                continue;
+            }
+
+            if matches!(status.health, Health::Healthy | Health::Degraded) {
+                if let Some(failure) = self.failures.remove(&service) {
+                    events.push(SupervisorEvent::Recovered {
+                        service,
+                        restarts: failure.restarts,
+                    });
+                }
+                continue;
+            }
+
+            let now = Instant::now();
+            let failure = self.failures.entry(service.clone()).or_insert_with(|| {
+                events.push(SupervisorEvent::Unhealthy {
+                    status: status.clone(),
+                });
+                Failure {
+                    restarts: 0,
+                    next_restart: now + config.backoff(0),
+                    escalated: false,
+                }
+            });
+            if failure.escalated || now < failure.next_restart {
+                continue;
+            }
+            if failure.restarts >= config.max_restarts {
+                failure.escalated = true;
+                events.push(SupervisorEvent::Escalated {
+                    service,
+                    restarts: failure.restarts,
+                });
+                continue;
+            }
+
+            failure.restarts += 1;
+            failure.next_restart = now + config.backoff(failure.restarts);
+            events.push(SupervisorEvent::Restarting {
+                service: service.clone(),
+                attempt: failure.restarts,
+            });
+            restarts.push(service);
+        }
+
+        restarts
+    }
+}
+
+impl OrbitStack {
+    /// Runs one round of supervision: probes every service and restarts those that are down or
+    /// unhealthy and due for a restart. Degraded services, such as syncing nodes, and
+    /// unreachable ones are left alone.
+    pub async fn supervise_once(
+        &self,
+        state: &mut SupervisorState,
+    ) -> Result<Vec<SupervisorEvent>, bollard::errors::Error> {
+        let mut events = Vec::new();
+        let restarts = state.assess(
+            &self.config.supervisor,
+            self.probe_all().await?,
+            &mut events,
+        );
+        self.restart_services(restarts, &mut events).await;
+        Ok(events)
+    }
+
+    /// Restarts the services picked by a round, reporting those that fail to restart.
+    async fn restart_services(&self, services: Vec<String>, events: &mut Vec<SupervisorEvent>) {
+        for service in services {
+            if let Err(e) = self.restart_service(&service).await {
+                events.push(SupervisorEvent::RestartFailed {
+                    service,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    /// Restarts the container of a service, starting it if it has exited.
+    pub async fn restart_service(&self, service: &str) -> Result<(), bollard::errors::Error> {
+        match self.containers.get(service) {
+            Some(container_id) => self.docker.restart_container(container_id, None).await,
+            None => Err(bollard::errors::Error::IOError {
+                err: std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Service {} is not part of the stack", service),
+                ),
+            }),
+        }
+    }
+
+    /// Supervises the stack in the background, every `supervisor.interval_secs`, for as long
+    /// as it is set in `stack`. The stack is locked only to take a snapshot of its services
+    /// and to restart those that fail, not while they are probed.
+    pub fn spawn_supervisor(stack: Arc<Mutex<Option<OrbitStack>>>) -> Supervisor {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let sender = events.clone();
+        let handle = tokio::spawn(async move {
+            let mut state = SupervisorState::default();
+            loop {
+                let (config, prober) = {
+                    let guard = stack.lock().await;
+                    let Some(stack) = guard.as_ref() else {
+                        info!("Stack removed, stopping its supervisor");
+                        return;
+                    };
+                    (stack.config.supervisor.clone(), stack.prober())
+                };
+
+                if config.enable {
+                    match prober.probe_all().await {
+                        Ok(statuses) => {
+                            let mut round = Vec::new();
+                            let restarts = state.assess(&config, statuses, &mut round);
+                            if !restarts.is_empty() {
+                                let guard = stack.lock().await;
+                                let Some(stack) = guard.as_ref() else {
+                                    info!("Stack removed, stopping its supervisor");
+                                    return;
+                                };
+                                stack.restart_services(restarts, &mut round).await;
+                            }
+                            for event in round {
+                                log_event(&event);
+                                // Events are dropped when nobody is subscribed
+                                let _ = sender.send(event);
+                            }
+                        }
+                        Err(e) => warn!("Supervision round failed: {}", e),
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(config.interval_secs)).await;
+            }
+        });
+        Supervisor { events, handle }
+    }
+}
+
+fn log_event(event: &SupervisorEvent) {
+    match event {
+        SupervisorEvent::Unhealthy { status } => warn!(
+            "Service {} is {:?}: {}",
+            status.service,
+            status.health,
+            status.message.as_deref().unwrap_or("no details")
+        ),
+        SupervisorEvent::Restarting { service, attempt } => {
+            info!("Restarting service {} (attempt {})", service, attempt)
+        }
+        SupervisorEvent::RestartFailed { service, error } => {
+            warn!("Failed to restart service {}: {}", service, error)
+        }
+        SupervisorEvent::Recovered { service, restarts } => {
+            info!("Service {} recovered after {} restarts", service, restarts)
+        }
+        SupervisorEvent::Escalated { service, restarts } => warn!(
+            "Service {} is still failing after {} restarts, giving up until it recovers",
+            service, restarts
+        ),
+    }
+}